@@ -114,6 +114,7 @@ pub fn try_repos(repos: Page<Repository>) -> octocrab::Result<Vec<List>> {
 
 // let fields = vec!["name", "url"];
 // let hash = repos.into_iter().map(|repo| to_hashmap(repo, fields)).collect::<Vec<_>>();
+#[allow(dead_code)]
 fn to_hashmap_with(repo: Repository, fields: Vec<&str>) -> HashMap<String, String> {
     let mut res = HashMap::new();
 
@@ -158,6 +159,7 @@ impl Default for List {
     }
 }
 
+#[allow(unused_macros)]
 macro_rules! check_field {
     ($field:ident, $ty:ty) => {
         match stringify!($field) {
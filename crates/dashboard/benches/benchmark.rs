@@ -22,9 +22,7 @@ lazy_static! {
 fn bench_try_main_ref(c: &mut Criterion) {
     let id = format!("{}_ref", *TRY_MAIN_REFACTOR);
     c.bench_function(&id, |b| {
-        b.iter(|| {
-            let _ = black_box(dashboard::app::try_main_refactor_v3("README.md"));
-        })
+        b.iter(|| black_box(dashboard::app::try_main_refactor_v3("README.md")))
     });
 }
 
@@ -33,9 +31,7 @@ fn bench_try_main_ref_batchsize(c: &mut Criterion) {
     c.bench_function(&id, |b| {
         b.iter_batched(
             || (),
-            |_| {
-                let _ = black_box(dashboard::app::try_main_refactor_v3("README.md"));
-            },
+            |_| black_box(dashboard::app::try_main_refactor_v3("README.md")),
             BatchSize::SmallInput,
         );
     });
@@ -0,0 +1,293 @@
+//! `cli` parses the command line of the `dashboard` binary into a [`Command`] and the [`Options`]
+//! it runs with.
+//!
+//! Each step of an update can run on its own, so scripts can fetch once and reuse the data:
+//!
+//! ```sh
+//! $ dashboard fetch --owner lloydlobo --json repos.json
+//! $ dashboard render --data repos.json --section tag_1
+//! $ dashboard check --data repos.json
+//! $ dashboard update --data repos.json --readme README.md --json README.json
+//! ```
+//!
+//! Without a subcommand, `dashboard` runs `update` with its default flags.
+
+use clap::{ArgAction, Args, FromArgMatches, Parser, Subcommand};
+use log::LevelFilter;
+use parser::findrepl::{self, Insert, MarkerFormat, Merge, Stamp};
+
+use crate::{
+    app::{Options, Restore},
+    constant::{BACKUPS_KEEP, PATH_JSON_GH_REPO_LIST, PATH_MD_OUTPUT},
+};
+
+/// `Cli` is the command line of `dashboard`.
+#[derive(Debug, Parser)]
+#[command(name = "dashboard", version, about = "Lists your GitHub repositories in README files")]
+pub struct Cli {
+    /// Log more, `-vv` to log everything.
+    #[arg(short, long, action = ArgAction::Count, global = true, conflicts_with = "quiet")]
+    pub verbose: u8,
+    /// Log less, `-qq` to log errors only, `-qqq` to log nothing.
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub quiet: u8,
+    /// Account whose repositories are listed, instead of the one `gh` is logged in as.
+    #[arg(long, global = true)]
+    pub owner: Option<String>,
+    /// When to color diagnostics and diffs.
+    #[arg(long, global = true, value_parser = ["auto", "always", "never"])]
+    pub color: Option<String>,
+    /// What to do, `update` if omitted.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    /// Returns the subcommand to run, `update` with its default flags if none was given.
+    pub fn into_command(self) -> Command {
+        self.command.unwrap_or_else(|| Command::Update(UpdateArgs::default()))
+    }
+
+    /// Returns the level logs are filtered at, `info` by default.
+    pub fn log_level(&self) -> LevelFilter {
+        match i16::from(self.verbose) - i16::from(self.quiet) {
+            i16::MIN..=-3 => LevelFilter::Off,
+            -2 => LevelFilter::Error,
+            -1 => LevelFilter::Warn,
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            2.. => LevelFilter::Trace,
+        }
+    }
+}
+
+/// `Command` is a subcommand of `dashboard`.
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Fetch the repositories and update the section of each file.
+    Update(UpdateArgs),
+    /// Fetch the repositories and write them to a JSON file.
+    Fetch {
+        /// File the repositories are written to.
+        #[arg(long, default_value = PATH_JSON_GH_REPO_LIST)]
+        json: String,
+    },
+    /// Print what the section of a file would be updated with, without writing anything.
+    Render {
+        /// Markdown file the section is in.
+        #[arg(long, default_value = PATH_MD_OUTPUT)]
+        readme: String,
+        #[command(flatten)]
+        #[allow(missing_docs)]
+        section: SectionArgs,
+    },
+    /// Fail and print the diff if the section of a file is out of date, e.g. in CI.
+    Check {
+        /// Markdown file the section is in.
+        #[arg(long, default_value = PATH_MD_OUTPUT)]
+        readme: String,
+        #[command(flatten)]
+        #[allow(missing_docs)]
+        section: SectionArgs,
+    },
+    /// List the sections of a file and report malformed markers.
+    Sections {
+        /// Markdown file to scan.
+        #[arg(long, default_value = PATH_MD_OUTPUT)]
+        readme: String,
+        /// Wording of the markers, see `--markers` of `update`.
+        #[arg(long, default_value = "section")]
+        markers: MarkerFormat,
+    },
+    /// Put a previous version of a file back from `.dashboard/backups`, or list them.
+    Restore {
+        /// Id of the backup, as listed by `--list`.
+        #[arg(conflicts_with_all = ["latest", "list"])]
+        id: Option<String>,
+        /// Restore the most recent backup.
+        #[arg(long, conflicts_with = "list")]
+        latest: bool,
+        /// List the backups, oldest first. The default without an id.
+        #[arg(long)]
+        list: bool,
+        /// File to restore.
+        #[arg(long, default_value = PATH_MD_OUTPUT)]
+        readme: String,
+    },
+}
+
+impl Command {
+    /// Returns the backup a `restore` command asks for.
+    pub fn restore(id: Option<String>, latest: bool) -> Restore {
+        match (id, latest) {
+            (Some(id), _) => Restore::Id(id),
+            (None, true) => Restore::Latest,
+            (None, false) => Restore::List,
+        }
+    }
+}
+
+/// `SectionArgs` selects the section of a file and the repositories it lists.
+#[derive(Debug, Clone, Args)]
+pub struct SectionArgs {
+    /// Name of the section.
+    #[arg(long, default_value = "tag_1")]
+    pub section: String,
+    /// Wording of the markers: `section`, `begin-end`, `name-start-end`, `blog-post-list`, or
+    /// `<start>|<end>` with `{name}` for the section name.
+    #[arg(long, default_value = "section")]
+    pub markers: MarkerFormat,
+    /// Read the repositories from a JSON file written by `fetch` instead of fetching them.
+    #[arg(long)]
+    pub data: Option<String>,
+}
+
+impl SectionArgs {
+    /// Returns the [`Options`] selecting this section, listing the repositories of `owner`.
+    pub fn options(&self, owner: Option<&str>) -> Options {
+        Options {
+            section: self.section.clone(),
+            markers: self.markers.clone(),
+            data: self.data.clone(),
+            owner: owner.map(str::to_string),
+            ..Options::default()
+        }
+    }
+}
+
+/// `UpdateArgs` are the flags of `update`.
+#[derive(Debug, Clone, Args)]
+pub struct UpdateArgs {
+    /// Markdown files to update, as paths or glob patterns. Files matched by a pattern are
+    /// skipped if they have no markers, while files named as is fail.
+    #[arg(long, default_value = PATH_MD_OUTPUT)]
+    pub readme: Vec<String>,
+    #[command(flatten)]
+    #[allow(missing_docs)]
+    pub section: SectionArgs,
+    /// File the repositories are written to, next to the README with a `.json` extension by
+    /// default. Only written when a single file is updated.
+    #[arg(long)]
+    pub json: Option<String>,
+    /// Print the diff of the update instead of writing any file.
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Where the section is created if a file has no markers yet: `never`, `append`,
+    /// `after:<heading>` or `before:<section>`.
+    #[arg(long, default_value = "never")]
+    pub insert: Insert,
+    /// Keep the lines of the section that end with a `<!-- keep -->` comment.
+    #[arg(long)]
+    pub keep_pinned: bool,
+    /// Stamp a hash of the content in the end marker, and `warn` or `refuse` when the section
+    /// was edited by hand since.
+    #[arg(long, default_value = "off")]
+    pub stamp: Stamp,
+    /// Number of previous versions of each file kept in `.dashboard/backups`, 0 to keep none.
+    #[arg(long, default_value_t = BACKUPS_KEEP)]
+    pub backups: usize,
+}
+
+/// The flags of `update` when none are given.
+impl Default for UpdateArgs {
+    fn default() -> Self {
+        let command = Self::augment_args(clap::Command::new("update"));
+        Self::from_arg_matches(&command.get_matches_from(["update"]))
+            .expect("default flags of `update` are valid")
+    }
+}
+
+impl UpdateArgs {
+    /// Returns the [`Options`] these flags describe, listing the repositories of `owner`.
+    pub fn options(&self, owner: Option<&str>) -> Options {
+        Options {
+            insert: self.insert.clone(),
+            merge: match self.keep_pinned {
+                true => Merge::KeepPinned,
+                false => Merge::Replace,
+            },
+            stamp: self.stamp,
+            backups: self.backups,
+            json: self.json.clone(),
+            ..self.section.options(owner)
+        }
+    }
+
+    /// Returns `true` if the update covers a single file, given as a path rather than a glob
+    /// pattern.
+    pub fn is_single_file(&self) -> bool {
+        match &self.readme[..] {
+            [readme] => !findrepl::is_glob(readme),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("dashboard").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn should_parse_subcommands_and_flags() {
+        Cli::command().debug_assert();
+
+        let cli = parse(&["-vv", "--owner", "octocat", "update", "--readme", "docs/*.md"]).unwrap();
+        assert_eq!(
+            (cli.log_level(), cli.owner.clone()),
+            (LevelFilter::Trace, Some("octocat".into()))
+        );
+        let Command::Update(update) = cli.into_command() else { panic!("expected `update`") };
+        assert!(!update.is_single_file());
+        let options = update.options(Some("octocat"));
+        assert_eq!((options.section.as_str(), options.backups), ("tag_1", BACKUPS_KEEP));
+
+        let cli =
+            parse(&["update", "--keep-pinned", "--stamp=refuse", "--markers", "begin-end", "-q"])
+                .unwrap();
+        assert_eq!(cli.log_level(), LevelFilter::Warn);
+        let Command::Update(update) = cli.into_command() else { panic!("expected `update`") };
+        let options = update.options(None);
+        assert_eq!((options.merge, options.stamp), (Merge::KeepPinned, Stamp::Refuse));
+        assert_eq!(options.markers, MarkerFormat::begin_end());
+
+        // Without a subcommand, `update` runs with its defaults.
+        let Command::Update(update) = parse(&[]).unwrap().into_command() else {
+            panic!("expected `update`")
+        };
+        assert!(update.is_single_file());
+        assert_eq!(update.options(None), Options::default());
+
+        let cli =
+            parse(&["check", "--section", "tag_2", "--data", "repos.json", "--owner=me"]).unwrap();
+        let owner = cli.owner.clone();
+        let Command::Check { readme, section } = cli.into_command() else {
+            panic!("expected `check`")
+        };
+        assert_eq!((readme.as_str(), owner.as_deref()), ("README.md", Some("me")));
+        assert_eq!(section.options(None).data.as_deref(), Some("repos.json"));
+
+        let Command::Restore { id, latest, .. } =
+            parse(&["restore", "--latest"]).unwrap().into_command()
+        else {
+            panic!("expected `restore`")
+        };
+        assert_eq!(Command::restore(id, latest), Restore::Latest);
+
+        for invalid in [
+            &["update", "--stamp", "always"][..],
+            &["-v", "-q"],
+            &["--keep-pinned"],
+            &["restore", "20261017T093000.123Z", "--latest"],
+            &["fetch", "--dry-run"],
+        ] {
+            assert!(parse(invalid).is_err(), "{invalid:?}");
+        }
+    }
+}
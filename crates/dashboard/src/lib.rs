@@ -49,7 +49,94 @@
 //!
 //! ### Usage
 //! ```sh
-//! $ CARGO_LOG=error cargo r -p dashboard
+//! $ cargo r -p dashboard -- update --readme README.md --section tag_1 --json README.json
+//! ```
+//!
+//! `update` is the default subcommand, so `cargo r -p dashboard` updates `README.md`. Each step
+//! also runs on its own, see `cli`: `fetch` writes the repositories to a JSON file, and `render`,
+//! `check` and `update` read them back with `--data` instead of fetching them again. `--owner`
+//! lists the repositories of another account, and `-v`/`-q` make the logs louder or quieter:
+//! ```sh
+//! $ cargo r -p dashboard -- fetch --owner lloydlobo --json repos.json
+//! $ cargo r -p dashboard -- -q render --data repos.json
+//! ```
+//!
+//! Preview the changes to `README.md` as a unified diff, without writing any file:
+//! ```sh
+//! $ cargo r -p dashboard -- update --dry-run
+//! ```
+//!
+//! Exit with an error and print the diff if `README.md` is out of date, e.g. in CI:
+//! ```sh
+//! $ cargo r -p dashboard -- check
+//! ```
+//!
+//! Choose what a section lists with attributes on its start marker, see `render`:
+//! ```md
+//! <!--START_SECTION:tag_1 sort=stars limit=10 topic=rust format=table-->
+//! ```
+//!
+//! Or lay it out with a Jinja-style template, inline in `.dashboard/config.json` or in a file, see
+//! `template`:
+//! ```md
+//! <!--START_SECTION:tag_1 sort=pushed limit=5 template=templates/recent.md.j2-->
+//! ```
+//!
+//! Errors point at the offending markers of the file. Colors are used on terminals unless
+//! `NO_COLOR` is set, or as forced with `--color=auto|always|never`.
+//!
+//! Update the section in every file matched by glob patterns, e.g. across a monorepo. Each file
+//! is reported as updated, unchanged, without markers or failed, and a failure doesn't stop the
+//! others:
+//! ```sh
+//! $ cargo r -p dashboard -- update --readme README.md --readme 'crates/*/README.md'
+//! ```
+//!
+//! A file without the markers is an error, unless `--insert` says where to create the section:
+//! at the end of the file, after a heading, or before another section:
+//! ```sh
+//! $ cargo r -p dashboard -- update --insert=append
+//! $ cargo r -p dashboard -- update --insert='after:## Projects' --readme 'docs/**/*.md'
+//! $ cargo r -p dashboard -- update --insert=before:tag_2
+//! ```
+//!
+//! Lines written by hand in the section are overwritten, unless `--keep-pinned` is given and
+//! they end with a `<!-- keep -->` comment. A pinned line stays under the repo it follows, or
+//! takes the place of the generated line of the repo it links to:
+//! ```md
+//! * [dashboard](https://github.com/lloydlobo/dashboard) — Build status of my repos.
+//!   Rewritten in Rust. <!-- keep -->
+//! * [mausam](https://github.com/lloydlobo/mausam) — Weather CLI. Archived. <!-- keep -->
+//! ```
+//!
+//! Detect sections edited by hand since the last run with `--stamp`, which writes a hash of the
+//! content in the end marker, e.g. `<!--END_SECTION:tag_1 hash=1a2b3c4d-->`. With `warn` an
+//! edited section is overwritten with a warning, with `refuse` it is left untouched and the run
+//! fails:
+//! ```sh
+//! $ cargo r -p dashboard -- update --stamp=refuse
+//! ```
+//!
+//! Take over files whose markers are worded by another generator with `--markers`, one of
+//! `section` (the default), `begin-end` for `<!-- BEGIN tag_1 -->`, `name-start-end` for
+//! `<!-- tag_1:start -->` and `blog-post-list` for `<!-- BLOG-POST-LIST:START -->`, or the start
+//! and end keywords separated by `|`, with `{name}` for the section name:
+//! ```sh
+//! $ cargo r -p dashboard -- update --markers=begin-end
+//! $ cargo r -p dashboard -- update --markers='repos:{name}:begin|repos:{name}:end'
+//! ```
+//!
+//! The previous version of a file is saved in `.dashboard/backups` before each write, keeping
+//! the 5 most recent ones, or as many as `--backups=N` says. Put one back with `restore`:
+//! ```sh
+//! $ cargo r -p dashboard -- restore --list
+//! $ cargo r -p dashboard -- restore --latest
+//! $ cargo r -p dashboard -- restore 20261017T093000.123Z --readme docs/README.md
+//! ```
+//!
+//! List the comment-block sections of a file and report malformed markers:
+//! ```sh
+//! $ cargo r -p dashboard -- sections --readme README.md
 //! ```
 //!
 //! ### Data - Github API
@@ -68,6 +155,9 @@
 //! * `description` - Description of the repository
 //------------------------------------------------------------------------------
 
+pub mod cli;
+pub(crate) mod render;
+pub(crate) mod template;
 pub(crate) mod util;
 
 //------------------------------------------------------------------------------
@@ -102,6 +192,15 @@ pub(crate) mod constant {
 
     /// Word count limit for description.
     pub(crate) const DESC_WC: usize = 60;
+
+    /// Directory the previous versions of updated files are kept in.
+    pub(crate) const PATH_BACKUPS: &str = ".dashboard/backups";
+
+    /// Path to the optional config of `dashboard`.
+    pub(crate) const PATH_CONFIG: &str = ".dashboard/config.json";
+
+    /// Number of previous versions kept for each file by default.
+    pub(crate) const BACKUPS_KEEP: usize = 5;
 }
 
 pub mod app {
@@ -110,15 +209,21 @@ pub mod app {
 
     use anyhow::anyhow;
     use crossbeam::thread;
-    use parser::findrepl::{self, CommentBlock};
+    use parser::{
+        findrepl::{
+            self, Backups, CommentBlock, CommentStyle, FileStatus, Insert, MarkerFormat, Merge,
+            ReplaceOutcome, Stamp,
+        },
+        printer, ParserError,
+    };
     use serde::{Deserialize, Serialize};
 
     use crate::{
         config,
-        constant::DESC_WC,
+        constant::{BACKUPS_KEEP, DESC_WC, PATH_BACKUPS, PATH_CONFIG},
         db::DB,
         gh::{GitCliOps, GitRepo, GitRepoListItem},
-        util,
+        render::{self, SectionOptions},
     };
 
     /// `Result<T, E>`
@@ -165,53 +270,436 @@ pub mod app {
     /// }
     /// ```
     pub async fn try_main_refactor_v3(file_path: &str) -> Result<(), AppError> {
-        let mut dashboard =
-            App { config: config::Config {}, db: DB { data: None, repo_list: None } };
+        try_main_with(file_path, &Options::default()).await
+    }
 
-        {
-            api::repos::list_user_repos().await.unwrap();
+    /// `Mode` selects what [`try_main_with`] does with the rendered markdown.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub enum Mode {
+        /// Update the markdown file and write the JSON data next to it.
+        #[default]
+        Write,
+        /// Print the diff the update would apply to the markdown file and write nothing.
+        DryRun,
+        /// Fail with the diff if the markdown file is out of date and write nothing.
+        Check,
+    }
+
+    /// `Options` tweaks how [`try_main_with`] and [`try_update_files`] treat the markdown files.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Options {
+        /// What is done with the rendered markdown. Batch updates always write.
+        pub mode: Mode,
+        /// Name of the section the repositories are listed in.
+        pub section: String,
+        /// Account whose repositories are listed, the one `gh` is logged in as if `None`.
+        pub owner: Option<String>,
+        /// JSON file written by [`try_fetch`] to read the repositories from, instead of fetching
+        /// them.
+        pub data: Option<String>,
+        /// Where [`try_main_with`] writes the repositories, next to the markdown file with a
+        /// `.json` extension if `None`.
+        pub json: Option<String>,
+        /// Where the section is created if a file has no markers yet.
+        pub insert: Insert,
+        /// Whether lines pinned with `<!-- keep -->` survive the update.
+        pub merge: Merge,
+        /// Whether a hash of the content is stamped in the end marker to detect hand edits.
+        pub stamp: Stamp,
+        /// How the start and end markers of the section are worded.
+        pub markers: MarkerFormat,
+        /// How many previous versions of each file are kept in `.dashboard/backups`, 0 to keep
+        /// none.
+        pub backups: usize,
+    }
+
+    impl Default for Options {
+        fn default() -> Self {
+            Self {
+                mode: Mode::default(),
+                section: "tag_1".to_string(),
+                owner: None,
+                data: None,
+                json: None,
+                insert: Insert::default(),
+                merge: Merge::default(),
+                stamp: Stamp::default(),
+                markers: MarkerFormat::default(),
+                backups: BACKUPS_KEEP,
+            }
         }
-        GitCliOps::fetch_repos_write_data(&mut dashboard.db)?;
+    }
+
+    impl Options {
+        fn block(&self) -> CommentBlock {
+            CommentBlock::new(self.section.clone())
+                .with_insert(self.insert.clone())
+                .with_merge(self.merge)
+                .with_stamp(self.stamp)
+                .with_format(self.markers.clone())
+        }
+
+        fn backups(&self) -> Backups {
+            Backups::new(PATH_BACKUPS, self.backups)
+        }
+
+        /// Returns the repositories read from [`Options::data`], or else fetched with `gh`.
+        fn repos(&self) -> Result<Vec<GitRepo>, AppError> {
+            match &self.data {
+                Some(path) => {
+                    let json =
+                        std::fs::read_to_string(path).map_err(|e| AppError::Io(Arc::new(e)))?;
+                    log::info!("Read repositories from {path}");
+                    Ok(serde_json::from_str(&json)?)
+                }
+                None => {
+                    let mut db = DB { data: None, repo_list: None };
+                    db.fetch_owner_repos(self.owner.as_deref())?;
+                    db.data.ok_or_else(|| AppError::UnwrapError("Failed to find data".to_string()))
+                }
+            }
+        }
+    }
+
+    /// Same as [`try_main_refactor_v3`], with `options` choosing whose repositories are listed in
+    /// which section, whether files are written, where the section is created if the file has no
+    /// markers yet, whether pinned lines are kept, how hand edits are detected, and how many
+    /// backups are kept.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error for the same reasons as [`try_main_refactor_v3`].
+    pub async fn try_main_with(file_path: &str, options: &Options) -> Result<(), AppError> {
+        let block = options.block();
+        let backups = options.backups();
+        let dashboard = App {
+            config: config::Config::load(PATH_CONFIG)?,
+            db: DB { data: Some(options.repos()?), repo_list: None },
+        };
 
+        match options.mode {
+            Mode::Write => (),
+            Mode::DryRun => {
+                return diff_markdown_file(&dashboard, file_path, &block);
+            }
+            Mode::Check => {
+                return check_markdown_file(&dashboard, file_path, &block);
+            }
+        }
+
+        let json_path = match &options.json {
+            Some(path) => path.clone(),
+            None => Path::new(file_path).with_extension("json").to_string_lossy().into_owned(),
+        };
         // Spawning the two operations into separate threads for parallel execution
-        thread::scope(|s| {
-            s.spawn(|_| update_markdown_file(dashboard.db.data.as_ref(), file_path));
-            s.spawn(|_| write_json_file(dashboard.db.data.as_ref(), file_path));
-        }) // PERF: Learn to handle error of type: `e: Box<dyn Any + Send>`.
-        .map_err(|e| AppError::CrossbeamError(anyhow!("{:?}", e)))?;
+        let panicked = |e| AppError::CrossbeamError(anyhow!("{:?}", e));
+        let (markdown, json) = thread::scope(|s| {
+            let markdown =
+                s.spawn(|_| update_markdown_file(&dashboard, file_path, &block, &backups));
+            let json = s.spawn(|_| write_json_file(dashboard.db.data.as_ref(), &json_path));
+            (markdown.join(), json.join())
+        })
+        .map_err(panicked)?;
+        markdown.map_err(panicked)??;
+        json.map_err(panicked)??;
 
         Ok(())
     }
 
-    // Replace the content of the file with the updated markdown list.
-    fn update_markdown_file(data: Option<&Vec<GitRepo>>, file_path: &str) -> Result<(), AppError> {
-        // If data is Some, convert `Vec<GitRepo>` into a list of GitRepoListItem.
-        let list = match data {
-            Some(data) => data.iter().map(GitRepoListItem::new).collect::<Vec<_>>(),
-            None => return Err(AppError::UnwrapError("Failed to find data".to_string())),
+    /// `try_update_files` fetches the repositories once and updates the section of every file
+    /// matched by the glob `patterns` in parallel, printing whether each file was updated, left
+    /// unchanged, had the section created as asked by `options`, was skipped for lack of markers,
+    /// or failed. Only files matched by a pattern are skipped; a file named as is without markers
+    /// fails. Files are backed up before they change.
+    ///
+    /// A file that fails doesn't stop the others; its error is reported once all are done.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a pattern is malformed, if the repositories can't be
+    /// fetched, or [`AppError::FilesFailed`] if any file couldn't be updated.
+    pub async fn try_update_files<S: AsRef<str>>(
+        patterns: &[S],
+        options: &Options,
+    ) -> Result<(), AppError> {
+        let paths = findrepl::expand_globs(patterns)?;
+        let dashboard = App {
+            config: config::Config::load(PATH_CONFIG)?,
+            db: DB { data: Some(options.repos()?), repo_list: None },
         };
+        let data = dashboard
+            .db
+            .data
+            .as_ref()
+            .ok_or_else(|| AppError::UnwrapError("Failed to find data".to_string()))?;
+
+        let block = options.block();
+        let backups = options.backups();
+        let reports = findrepl::replace_in_files(&paths, &block, Some(&backups), |_, attrs| {
+            match SectionOptions::from_attributes(attrs) {
+                Ok(options) => match options.render(data, &dashboard.config) {
+                    Ok(text) => Ok(text),
+                    Err(e) => Err(ParserError::LogicBug(e.to_string())),
+                },
+                Err(AppError::InvalidAttribute(e)) => Err(ParserError::InvalidAttribute(e)),
+                Err(e) => Err(ParserError::LogicBug(e.to_string())),
+            }
+        });
 
-        let (text, block) = rayon::join(
-            || list.iter().map(fmt_markdown_list_item).collect::<Vec<_>>().join("\n"),
-            || CommentBlock::new("tag_1".to_string()),
+        for report in &reports {
+            println!("{report}");
+        }
+        let count = |f: fn(&FileStatus) -> bool| reports.iter().filter(|r| f(&r.status)).count();
+        let failed = count(FileStatus::is_error);
+        println!(
+            "{} updated, {} created, {} unchanged, {} without markers, {failed} failed",
+            count(|s| matches!(s, FileStatus::Updated)),
+            count(|s| matches!(s, FileStatus::Created)),
+            count(|s| matches!(s, FileStatus::Unchanged)),
+            count(|s| matches!(s, FileStatus::NoMarkers)),
         );
 
-        findrepl::replace_par(&text, block, Path::new(file_path)).map_err(AppError::ParserError)?;
+        let total = reports.len();
+        for report in reports {
+            if let FileStatus::Error(e) = report.status {
+                report_error(&AppError::ParserError(e));
+            }
+        }
+        match failed {
+            0 => Ok(()),
+            failed => Err(AppError::FilesFailed { failed, total }),
+        }
+    }
+
+    /// `try_fetch` fetches the repositories listed by `options` and writes them to `json_path`,
+    /// for [`Options::data`] to read them back.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the repositories can't be fetched or the file can't
+    /// be written.
+    pub fn try_fetch(json_path: &str, options: &Options) -> Result<(), AppError> {
+        let repos = options.repos()?;
+        write_json_file(Some(&repos), json_path)
+    }
+
+    /// `try_render` prints the content the section of `options` in the file at `file_path` would
+    /// be updated with, without writing anything.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the repositories can't be fetched, or the section
+    /// can't be found or rendered.
+    pub fn try_render(file_path: &str, options: &Options) -> Result<(), AppError> {
+        let dashboard = App {
+            config: config::Config::load(PATH_CONFIG)?,
+            db: DB { data: Some(options.repos()?), repo_list: None },
+        };
+        println!("{}", render_markdown_section(&dashboard, file_path, &options.block())?);
+
+        Ok(())
+    }
+
+    /// `read_markdown_list` returns the repositories currently listed in the section of `block`
+    /// in the file at `file_path`, to compare them with a new fetch. Lines of the section that
+    /// aren't list items, such as manual annotations, are skipped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file can't be read or the markers of its
+    /// section are not found.
+    pub fn read_markdown_list(
+        file_path: &str,
+        block: &CommentBlock,
+    ) -> Result<Vec<GitRepoListItem>, AppError> {
+        let text = findrepl::extract_file(block, Path::new(file_path))?;
+
+        Ok(render::parse_markdown_list(&text))
+    }
+
+    /// `Restore` selects the backup [`restore`] puts back, or asks it to list them.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Restore {
+        /// Print the backups of the file, oldest first.
+        List,
+        /// Restore the most recent backup.
+        Latest,
+        /// Restore the backup with this id, as printed by [`Restore::List`].
+        Id(String),
+    }
+
+    /// `restore` puts a previous version of the file at `file_path` back from
+    /// `.dashboard/backups`, or lists the versions kept. The current version is backed up first,
+    /// so a restore can be undone too.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the backup asked for doesn't exist, or if the
+    /// backups or the file can't be read or written.
+    pub fn restore(file_path: &str, action: &Restore) -> Result<(), AppError> {
+        let path = Path::new(file_path);
+        let backups = Options::default().backups();
+        let backup = match action {
+            Restore::List => {
+                for backup in backups.list(path)? {
+                    let len = std::fs::metadata(&backup.path).map_or(0, |m| m.len());
+                    println!("{}\t{len} bytes", backup.id);
+                }
+                return Ok(());
+            }
+            Restore::Latest => backups.list(path)?.pop().ok_or_else(|| {
+                ParserError::BackupNotFound { id: "latest".to_string(), path: path.to_path_buf() }
+            })?,
+            Restore::Id(id) => backups.find(path, id)?,
+        };
+        backups.restore(path, &backup)?;
+        println!("Restored {file_path} from backup {}", backup.id);
+
+        Ok(())
+    }
+
+    /// `list_sections` prints every comment-block section of the file at `file_path` with its
+    /// line range, indenting nested sections under the section they are in. Markers are worded
+    /// after `markers`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file can't be read or if its sections have
+    /// structural problems.
+    pub fn list_sections(file_path: &str, markers: &MarkerFormat) -> Result<(), AppError> {
+        let buf = std::fs::read_to_string(file_path).map_err(|e| AppError::Io(Arc::new(e)))?;
+        let style = CommentStyle::from_path(Path::new(file_path));
+        let scan = findrepl::scan_sections_with_format(&buf, &style, markers);
+
+        fn print_tree(trees: &[findrepl::SectionTree], depth: usize) {
+            for tree in trees {
+                let section = &tree.section;
+                let indent = "  ".repeat(depth);
+                println!(
+                    "{indent}{}\tlines {}-{}",
+                    section.name,
+                    section.start + 1,
+                    section.end + 1
+                );
+                print_tree(&tree.children, depth + 1);
+            }
+        }
+        print_tree(&scan.tree(), 0);
+
+        scan.validate()
+            .map(|_| ())
+            .map_err(|e| AppError::ParserError(e.with_path(Path::new(file_path))))
+    }
+
+    /// `report_error` prints `error` to stderr through [`printer::print_diagnostic`], with the
+    /// source lines and markers it is about for parser errors.
+    pub fn report_error(error: &AppError) {
+        let diagnostic = match error {
+            AppError::ParserError(e) | AppError::FindReplaceError(e) => {
+                printer::Diagnostic::from(e)
+            }
+            e => printer::Diagnostic::new(e.to_string()),
+        };
+        if printer::print_diagnostic(&diagnostic).is_err() {
+            eprintln!("error: {error}");
+        }
+    }
+
+    // Replace the content of the file with the updated markdown list.
+    fn update_markdown_file(
+        dashboard: &App,
+        file_path: &str,
+        block: &CommentBlock,
+        backups: &Backups,
+    ) -> Result<(), AppError> {
+        let text = render_markdown_section(dashboard, file_path, block)?;
+        // The section may not exist yet if it is to be created.
+        if let Ok(previous) = read_markdown_list(file_path, block) {
+            let current = render::parse_markdown_list(&text);
+            let (added, removed) = render::list_changes(&previous, &current);
+            for name in added {
+                log::info!("Listing {name} in {file_path}");
+            }
+            for name in removed {
+                log::info!("No longer listing {name} in {file_path}");
+            }
+        }
+
+        findrepl::replace_with_backups(&text, block.clone(), Path::new(file_path), backups)
+            .map_err(AppError::ParserError)?;
         log::info!("Updated git repo list in file {}", file_path);
 
         Ok(())
     }
 
-    /// Open or create a file and write the data to it in JSON format.
-    fn write_json_file(data: Option<&Vec<GitRepo>>, file_path: &str) -> Result<(), AppError> {
-        let path = util::replace_file_extension(file_path, "json");
+    // Print the diff the updated markdown list would make to the file, without writing it.
+    fn diff_markdown_file(
+        dashboard: &App,
+        file_path: &str,
+        block: &CommentBlock,
+    ) -> Result<(), AppError> {
+        let text = render_markdown_section(dashboard, file_path, block)?;
+
+        let path = Path::new(file_path);
+        match findrepl::replace_dry_run(&text, block, path).map_err(AppError::ParserError)? {
+            ReplaceOutcome::Unchanged => log::info!("No changes to git repo list in {}", file_path),
+            ReplaceOutcome::Changed { diff } => {
+                printer::print_diff(&diff, path).map_err(AppError::ParserError)?
+            }
+        }
+
+        Ok(())
+    }
+
+    // Fail if the markdown list in the file differs from the rendered one, printing the diff.
+    fn check_markdown_file(
+        dashboard: &App,
+        file_path: &str,
+        block: &CommentBlock,
+    ) -> Result<(), AppError> {
+        let text = render_markdown_section(dashboard, file_path, block)?;
+
+        let path = Path::new(file_path);
+        match findrepl::check(&text, block, path) {
+            Ok(()) => log::info!("Git repo list in {} is up to date", file_path),
+            Err(e) => {
+                if let ParserError::StaleSection { diff, .. } = &e {
+                    printer::print_diff(diff, path).map_err(AppError::ParserError)?;
+                }
+                return Err(AppError::ParserError(e));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Render the markdown list of repositories for the section of `block`, as described by the
+    /// attributes of its start marker in the file at `file_path` and the config of `dashboard`.
+    fn render_markdown_section(
+        dashboard: &App,
+        file_path: &str,
+        block: &CommentBlock,
+    ) -> Result<String, AppError> {
+        let data = match dashboard.db.data.as_ref() {
+            Some(data) => data,
+            None => return Err(AppError::UnwrapError("Failed to find data".to_string())),
+        };
+        let attrs = findrepl::section_attributes(block, Path::new(file_path))
+            .map_err(AppError::ParserError)?;
+
+        SectionOptions::from_attributes(&attrs)?.render(data, &dashboard.config)
+    }
+
+    /// Open or create the file at `path` and write the data to it in JSON format.
+    fn write_json_file(data: Option<&Vec<GitRepo>>, path: &str) -> Result<(), AppError> {
         let (file, data) = rayon::join(
             || {
                 OpenOptions::new()
                     .read(true)
                     .write(true)
                     .create(true)
-                    .open(&path)
+                    .truncate(true)
+                    .open(path)
                     .map_err(|e| AppError::Io(Arc::new(e)))
             },
             || {
@@ -222,7 +710,7 @@ pub mod app {
             },
         );
 
-        log::info!("Writing git repo list to file {}", &path);
+        log::info!("Writing git repo list to file {}", path);
         serde_json::to_writer_pretty(file?, data?).map_err(AppError::SerdeError)
     }
 
@@ -252,12 +740,26 @@ pub mod app {
         /// An error occurred in the code logic
         #[error("Error in logic: {0}")]
         LogicBug(String),
-        /// An error occurred while parsing input
-        #[error("Parsing error: {0}")]
+        /// An error occurred while parsing input, reported as is so its location is kept
+        #[error(transparent)]
         ParserError(#[from] parser::ParserError),
         /// An error occurred in parser crate.
         #[error("parser package I/O error: {0}")]
         FindReplaceError(parser::ParserError),
+        /// Some files of a batch update couldn't be updated
+        #[error("{failed} of {total} files could not be updated")]
+        FilesFailed {
+            /// Number of files that failed.
+            failed: usize,
+            /// Number of files matched.
+            total: usize,
+        },
+        /// An attribute of a section marker has an invalid value
+        #[error("Invalid section attribute: {0}")]
+        InvalidAttribute(String),
+        /// A section template couldn't be found or rendered
+        #[error("Template error: {0:#}")]
+        TemplateError(#[from] minijinja::Error),
         /// An error occurred with a regular expression
         #[error("Regex error")]
         RegexError(#[from] regex::Error),
@@ -301,6 +803,32 @@ pub mod app {
         // #[error("GitHub CLI error: {0}")]
         // Reqwest(#[from] reqwest::Error),
     }
+
+    #[cfg(test)]
+    mod tests {
+        use std::fs;
+
+        use tempfile::tempdir;
+
+        use super::*;
+
+        #[tokio::test]
+        async fn should_fail_to_update_a_file_without_markers() {
+            let dir = tempdir().unwrap();
+            let (readme, data) = (dir.path().join("README.md"), dir.path().join("repos.json"));
+            fs::write(&readme, "# Projects\n").unwrap();
+            fs::write(&data, "[]").unwrap();
+            let options =
+                Options { data: Some(data.to_string_lossy().into_owned()), ..Options::default() };
+
+            let err = try_main_with(&readme.to_string_lossy(), &options).await.unwrap_err();
+            assert!(
+                matches!(err, AppError::ParserError(ParserError::MarkerNotFound { .. })),
+                "{err:?}"
+            );
+            assert_eq!(fs::read_to_string(&readme).unwrap(), "# Projects\n");
+        }
+    }
 }
 
 //------------------------------------------------------------------------------
@@ -335,13 +863,20 @@ pub(crate) mod db {
     impl GitCliOps for DB {
         /// Assigns the fetched response to `self.data`.
         fn fetch_repos_write_data(&mut self) -> Result<(), AppError> {
+            self.fetch_owner_repos(None)
+        }
+
+        /// Assigns the fetched response to `self.data`.
+        #[allow(unexpected_cfgs)] // Emitted by `xshell::cmd!`.
+        fn fetch_owner_repos(&mut self, owner: Option<&str>) -> Result<(), AppError> {
             let sh = Shell::new().map_err(AppError::XshellIo)?;
             let opts_json_args: String = ARGS_GH_REPO_LIST_JSON.join(",");
 
-            let repos: String = cmd!(sh, "gh repo list --source -L 999 --json {opts_json_args}")
-                .read()
-                .map_err(AppError::XshellIo)?;
-            log::info!("Fetched repositories with command: `gh repo list`");
+            let repos: String =
+                cmd!(sh, "gh repo list {owner...} --source -L 999 --json {opts_json_args}")
+                    .read()
+                    .map_err(AppError::XshellIo)?;
+            log::info!("Fetched repositories of {} with `gh repo list`", owner.unwrap_or("you"));
 
             let repos: Vec<GitRepo> = serde_json::from_str(&repos).map_err(AppError::SerdeError)?;
             log::info!("Deserialized {} repositories", repos.len());
@@ -378,7 +913,7 @@ pub(crate) mod gh {
     }
 
     /// Custom data structure to parse into markdown list item.
-    #[derive(Clone, Debug, Deserialize, Serialize)]
+    #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
     #[serde(rename_all = "camelCase")] // https://serde.rs/attr-rename.html
     pub struct GitRepoListItem {
         /// Repository name.
@@ -420,16 +955,48 @@ pub(crate) mod gh {
         /// * [`serde_json`] - conversion can fail if the structure of the input does not match the
         ///   structure expected by `Vec<GitRepo>`.
         fn fetch_repos_write_data(&mut self) -> Result<(), AppError>;
+
+        /// Same as [`GitCliOps::fetch_repos_write_data`], for the repositories of `owner`, or of
+        /// the account `gh` is logged in as if `None`.
+        ///
+        /// # Errors
+        ///
+        /// This function will return an error for the same reasons as
+        /// [`GitCliOps::fetch_repos_write_data`].
+        fn fetch_owner_repos(&mut self, owner: Option<&str>) -> Result<(), AppError>;
     }
 }
 
 //------------------------------------------------------------------------------
 
 pub(crate) mod config {
+    use std::{collections::BTreeMap, fs, io::ErrorKind, sync::Arc};
+
     use serde::{Deserialize, Serialize};
 
-    #[derive(Clone, Debug, Deserialize, Serialize)]
-    pub struct Config {}
+    use crate::app::AppError;
+
+    /// `Config` is read from `.dashboard/config.json`, where every field is optional.
+    #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+    #[serde(default)]
+    pub struct Config {
+        /// Template of the sections that set neither a `template` nor a `format`, see
+        /// [`crate::template`].
+        pub template: Option<String>,
+        /// Inline templates, by the name sections refer to them with.
+        pub templates: BTreeMap<String, String>,
+    }
+
+    impl Config {
+        /// Reads the config at `path`, or returns the default one if there is no such file.
+        pub fn load(path: &str) -> Result<Self, AppError> {
+            match fs::read_to_string(path) {
+                Ok(json) => Ok(serde_json::from_str(&json)?),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+                Err(e) => Err(AppError::Io(Arc::new(e))),
+            }
+        }
+    }
 }
 
 //------------------------------------------------------------------------------
@@ -457,7 +1024,7 @@ pub(crate) mod archive {
 
     // pub fn try_main_refactor_v2(file_path: &str) -> Result<(), AppError> {
     //     let mut dashboard =
-    //         App { config: config::Config {}, db: DB { data: None, repo_list: None } };
+    //         App { config: config::Config::default(), db: DB { data: None, repo_list: None } };
     //     GitCliOps::fetch_repos_write_data(&mut dashboard.db)?;
     //     update_markdown_file(dashboard.db.data.as_ref(), file_path)?;
     //     write_json_file(dashboard.db.data.as_ref(), file_path)?;
@@ -466,7 +1033,7 @@ pub(crate) mod archive {
 
     pub fn try_main_refactor() -> Result<(), AppError> {
         let mut dashboard =
-            App { config: config::Config {}, db: DB { data: None, repo_list: None } };
+            App { config: config::Config::default(), db: DB { data: None, repo_list: None } };
 
         GitCliOps::fetch_repos_write_data(&mut dashboard.db)?;
 
@@ -497,6 +1064,7 @@ pub(crate) mod archive {
                 .read(true)
                 .write(true)
                 .create(true)
+                .truncate(true)
                 .open(PATH_JSON_GH_REPO_LIST)
                 .map_err(|e| AppError::Io(Arc::new(e)))?;
             let data = match dashboard.db.data.as_ref() {
@@ -514,7 +1082,7 @@ pub(crate) mod archive {
 
     pub(crate) fn try_main() -> Result<(), AppError> {
         let mut dashboard =
-            App { config: config::Config {}, db: DB { data: None, repo_list: None } };
+            App { config: config::Config::default(), db: DB { data: None, repo_list: None } };
 
         dashboard.db.fetch_repos_write_data()?;
 
@@ -550,6 +1118,7 @@ pub(crate) mod archive {
                 .read(true)
                 .write(true)
                 .create(true)
+                .truncate(true)
                 .open(PATH_JSON_GH_REPO_LIST)
                 .map_err(|e| AppError::Io(Arc::new(e)))?;
             let data: &Vec<GitRepo> = dashboard
@@ -5,8 +5,11 @@
 
 #![deny(missing_debug_implementations, missing_docs)]
 
-use anyhow::anyhow;
-use dashboard::app::{self, AppError};
+use clap::Parser;
+use dashboard::{
+    app,
+    cli::{Cli, Command},
+};
 use lazy_static::lazy_static;
 
 pub use self::app::*;
@@ -19,6 +22,8 @@ lazy_static! {
 
 /// `main` entrypoint.
 ///
+/// Exits with code 2 if the command line is malformed, and 1 if the command fails.
+///
 /// # Errors
 ///
 /// This function will return an error if .
@@ -26,13 +31,53 @@ lazy_static! {
 pub async fn main() -> app::Result<(), AppError> {
     dotenv::dotenv().ok();
     let start = std::time::Instant::now();
-    pretty_env_logger::env_logger::builder()
-        .filter_level(log::LevelFilter::Info)
-        .filter_level(log::LevelFilter::Debug)
-        .init();
+    let cli = Cli::parse();
+    pretty_env_logger::env_logger::builder().filter_level(cli.log_level()).init();
+
+    if let Some(value) = &cli.color {
+        match parser::printer::parse_color_choice(value) {
+            Ok(choice) => parser::printer::set_color_choice(choice),
+            Err(e) => {
+                app::report_error(&AppError::ParserError(e));
+                std::process::exit(2)
+            }
+        }
+    }
 
-    if let Err(e) = app::try_main_refactor_v3("README.md").await {
-        eprintln!("{}", anyhow!(e));
+    let owner = cli.owner.clone();
+    let owner = owner.as_deref();
+    let result = match cli.into_command() {
+        Command::Update(update) => {
+            let mut options = update.options(owner);
+            match update.is_single_file() {
+                true => {
+                    if update.dry_run {
+                        options.mode = Mode::DryRun;
+                    }
+                    app::try_main_with(&update.readme[0], &options).await
+                }
+                false if update.dry_run || update.json.is_some() => Err(AppError::LogicBug(
+                    "`--dry-run` and `--json` take a single `--readme` file".to_string(),
+                )),
+                false => app::try_update_files(&update.readme, &options).await,
+            }
+        }
+        Command::Fetch { json } => {
+            let options = Options { owner: owner.map(str::to_string), ..Options::default() };
+            app::try_fetch(&json, &options)
+        }
+        Command::Render { readme, section } => app::try_render(&readme, &section.options(owner)),
+        Command::Check { readme, section } => {
+            let options = Options { mode: Mode::Check, ..section.options(owner) };
+            app::try_main_with(&readme, &options).await
+        }
+        Command::Sections { readme, markers } => app::list_sections(&readme, &markers),
+        Command::Restore { id, latest, readme, .. } => {
+            app::restore(&readme, &Command::restore(id, latest))
+        }
+    };
+    if let Err(e) = result {
+        app::report_error(&e);
         std::process::exit(1)
     }
 
@@ -0,0 +1,303 @@
+//! `render` turns the attributes of a section marker into the markdown written in that section.
+//!
+//! A section describes what it should list right in its start marker:
+//!
+//! ```md
+//! <!--START_SECTION:tag_1 sort=stars order=desc limit=10 topic=rust format=table-->
+//! <!--END_SECTION:tag_1-->
+//! ```
+//!
+//! * `sort` - `stars`, `name`, `created`, `updated` or `pushed`. Defaults to the order of `gh`.
+//! * `order` - `asc` or `desc`. Defaults to `desc` for stars and dates, `asc` for names.
+//! * `limit` - Maximum number of repositories.
+//! * `topic` - Only list repositories tagged with this topic.
+//! * `format` - `list` (default) or `table`.
+//! * `template` - Template the section is rendered from instead, see [`template`].
+//!
+//! [`parse_markdown_list`] reads a rendered list back, and [`list_changes`] compares the previous
+//! content of a section with a new fetch.
+
+use std::cmp::Ordering;
+
+use lazy_static::lazy_static;
+use parser::findrepl::Attributes;
+use regex::Regex;
+
+use crate::{
+    app::{fmt_markdown_list_item, AppError},
+    config::Config,
+    gh::{GitRepo, GitRepoListItem},
+    template,
+};
+
+/// Field the repositories of a section are sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SortKey {
+    Stars,
+    Name,
+    Created,
+    Updated,
+    Pushed,
+}
+
+/// Markdown layout of a section.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    #[default]
+    List,
+    Table,
+}
+
+/// `SectionOptions` holds the filtering, sorting, limit and format or template of a section.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct SectionOptions {
+    pub(crate) sort: Option<SortKey>,
+    pub(crate) descending: Option<bool>,
+    pub(crate) limit: Option<usize>,
+    pub(crate) topic: Option<String>,
+    pub(crate) format: Option<Format>,
+    pub(crate) template: Option<String>,
+}
+
+impl SectionOptions {
+    /// Reads the options from the attributes of a start marker. Unknown keys are ignored with a
+    /// warning, so a marker can carry attributes meant for other tools.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AppError::InvalidAttribute`] if a known key has an invalid
+    /// value.
+    pub(crate) fn from_attributes(attrs: &Attributes) -> Result<Self, AppError> {
+        let invalid = |key: &str, value: &str| {
+            AppError::InvalidAttribute(format!("unexpected value `{value}` for `{key}`"))
+        };
+        let mut options = Self::default();
+
+        for (key, value) in attrs {
+            match key.as_str() {
+                "sort" => {
+                    options.sort = Some(match value.as_str() {
+                        "stars" => SortKey::Stars,
+                        "name" => SortKey::Name,
+                        "created" => SortKey::Created,
+                        "updated" => SortKey::Updated,
+                        "pushed" => SortKey::Pushed,
+                        _ => return Err(invalid(key, value)),
+                    })
+                }
+                "order" => {
+                    options.descending = Some(match value.as_str() {
+                        "asc" => false,
+                        "desc" => true,
+                        _ => return Err(invalid(key, value)),
+                    })
+                }
+                "limit" => options.limit = Some(value.parse().map_err(|_| invalid(key, value))?),
+                "topic" => options.topic = Some(value.clone()),
+                "format" => {
+                    options.format = Some(match value.as_str() {
+                        "list" => Format::List,
+                        "table" => Format::Table,
+                        _ => return Err(invalid(key, value)),
+                    })
+                }
+                "template" => options.template = Some(value.clone()),
+                _ => log::warn!("Ignoring unknown section attribute `{key}`"),
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Filters, sorts and limits `repos`, then renders them with the template of the section, in
+    /// its format, or with the template of `config` if it has neither.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`AppError::TemplateError`] if the template can't be found or
+    /// rendered.
+    pub(crate) fn render(&self, repos: &[GitRepo], config: &Config) -> Result<String, AppError> {
+        let mut repos: Vec<&GitRepo> = repos
+            .iter()
+            .filter(|repo| match &self.topic {
+                Some(topic) => repo
+                    .repository_topics
+                    .as_ref()
+                    .is_some_and(|topics| topics.iter().any(|t| t.name == *topic)),
+                None => true,
+            })
+            .collect();
+
+        if let Some(key) = self.sort {
+            let descending = self.descending.unwrap_or(key != SortKey::Name);
+            repos.sort_by(|a, b| {
+                let ordering = compare(key, a, b);
+                match descending {
+                    true => ordering.reverse(),
+                    false => ordering,
+                }
+            });
+        } else if self.descending == Some(false) {
+            repos.reverse();
+        }
+        repos.truncate(self.limit.unwrap_or(usize::MAX));
+
+        let template = match self.format {
+            None => self.template.as_ref().or(config.template.as_ref()),
+            Some(_) => self.template.as_ref(),
+        };
+        if let Some(name) = template {
+            return template::render(&template::load(name, config)?, &repos);
+        }
+
+        let items = repos.into_iter().map(GitRepoListItem::new);
+        Ok(match self.format.unwrap_or_default() {
+            Format::List => {
+                items.map(|i| fmt_markdown_list_item(&i)).collect::<Vec<_>>().join("\n")
+            }
+            Format::Table => {
+                let rows = items.map(|i| {
+                    format!("| [{}]({}) | {} |", i.name, i.url, i.description.replace('|', "\\|"))
+                });
+                ["| Repository | Description |", "| --- | --- |"]
+                    .into_iter()
+                    .map(String::from)
+                    .chain(rows)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        })
+    }
+}
+
+lazy_static! {
+    /// Matches a list item written by [`fmt_markdown_list_item`].
+    static ref RE_LIST_ITEM: Regex =
+        Regex::new(r"^[*-] \[(?P<name>[^\]]+)\]\((?P<url>[^)\s]+)\)(?: — (?P<description>.*))?$")
+            .unwrap();
+}
+
+/// Parses a `* [name](url) — description` line written by [`fmt_markdown_list_item`] back into a
+/// [`GitRepoListItem`]. A description truncated when rendered keeps its trailing `...`.
+pub(crate) fn parse_markdown_list_item(line: &str) -> Option<GitRepoListItem> {
+    let caps = RE_LIST_ITEM.captures(line.trim())?;
+
+    Some(GitRepoListItem {
+        name: caps["name"].to_string(),
+        url: caps["url"].to_string(),
+        description: caps.name("description").map_or("", |m| m.as_str()).trim().to_string(),
+    })
+}
+
+/// Parses the list items of the content of a section, skipping every other line, such as manual
+/// annotations or a table.
+pub(crate) fn parse_markdown_list(text: &str) -> Vec<GitRepoListItem> {
+    text.lines().filter_map(parse_markdown_list_item).collect()
+}
+
+/// Returns the names of the repositories of `current` missing from `previous`, and those of
+/// `previous` missing from `current`, each in the order they are listed.
+pub(crate) fn list_changes<'a>(
+    previous: &'a [GitRepoListItem],
+    current: &'a [GitRepoListItem],
+) -> (Vec<&'a str>, Vec<&'a str>) {
+    let missing = |items: &'a [GitRepoListItem], from: &[GitRepoListItem]| {
+        items
+            .iter()
+            .filter(|i| !from.iter().any(|f| f.name == i.name))
+            .map(|i| i.name.as_str())
+            .collect()
+    };
+
+    (missing(current, previous), missing(previous, current))
+}
+
+// Dates from `gh` are RFC 3339 in UTC, so they sort correctly as strings.
+fn compare(key: SortKey, a: &GitRepo, b: &GitRepo) -> Ordering {
+    match key {
+        SortKey::Stars => a.stargazer_count.cmp(&b.stargazer_count),
+        SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        SortKey::Created => a.created_at.cmp(&b.created_at),
+        SortKey::Updated => a.updated_at.cmp(&b.updated_at),
+        SortKey::Pushed => a.pushed_at.cmp(&b.pushed_at),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::findrepl::parse_attributes;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{constant::DESC_WC, gh::RepositoryTopic};
+
+    fn repo(name: &str, stars: u32, topic: &str) -> GitRepo {
+        GitRepo {
+            created_at: String::new(),
+            description: String::new(),
+            disk_usage: 0,
+            id: name.to_string(),
+            name: name.to_string(),
+            pushed_at: String::new(),
+            repository_topics: Some(vec![RepositoryTopic { name: topic.to_string() }]),
+            ssh_url: String::new(),
+            stargazer_count: stars,
+            updated_at: String::new(),
+            url: format!("https://github.com/u/{name}"),
+        }
+    }
+
+    #[test]
+    fn should_render_with_marker_attributes() {
+        let repos = vec![repo("a", 1, "rust"), repo("b", 5, "go"), repo("c", 3, "rust")];
+
+        let attrs = parse_attributes("sort=stars limit=1 topic=rust").unwrap();
+        let options = SectionOptions::from_attributes(&attrs).unwrap();
+        assert_eq!(
+            options.render(&repos, &Config::default()).unwrap(),
+            "* [c](https://github.com/u/c)"
+        );
+
+        let attrs = parse_attributes("sort=name order=desc format=table").unwrap();
+        let options = SectionOptions::from_attributes(&attrs).unwrap();
+        assert_eq!(
+            options.render(&repos, &Config::default()).unwrap(),
+            "| Repository | Description |\n| --- | --- |\n\
+             | [c](https://github.com/u/c) |  |\n\
+             | [b](https://github.com/u/b) |  |\n\
+             | [a](https://github.com/u/a) |  |"
+        );
+    }
+
+    #[test]
+    fn should_parse_rendered_list_items() {
+        let mut described = repo("b", 0, "rust");
+        described.description = "A [small] tool — with (parens)".to_string();
+        let mut long = repo("c", 0, "rust");
+        long.description = "word ".repeat(20);
+        let repos = vec![repo("a", 0, "rust"), described, long];
+
+        let items: Vec<_> = repos.iter().map(GitRepoListItem::new).collect();
+        let text = SectionOptions::default().render(&repos, &Config::default()).unwrap();
+        let parsed = parse_markdown_list(&format!("{text}\n\n> Note: kept by hand\n"));
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[..2], items[..2]);
+        assert_eq!(parsed[2].description, format!("{}...", &items[2].description[..DESC_WC]));
+
+        assert_eq!(parse_markdown_list_item("| [a](https://github.com/u/a) |  |"), None);
+
+        let previous = parse_markdown_list("* [a](https://github.com/u/a)\n* [b](u/b) — old");
+        assert_eq!(list_changes(&previous, &parsed), (vec!["c"], vec![]));
+        assert_eq!(list_changes(&parsed, &previous[..1]), (vec![], vec!["b", "c"]));
+    }
+
+    #[test]
+    fn should_reject_invalid_attribute_values() {
+        for raw in ["sort=forks", "order=up", "limit=-1", "format=csv"] {
+            let attrs = parse_attributes(raw).unwrap();
+            assert!(SectionOptions::from_attributes(&attrs).is_err(), "{raw}");
+        }
+        let attrs = parse_attributes("color=red").unwrap();
+        assert_eq!(SectionOptions::from_attributes(&attrs).unwrap(), SectionOptions::default());
+    }
+}
@@ -0,0 +1,251 @@
+//! `template` renders the content of a section from a Jinja-style template, for layouts the
+//! `format` attribute doesn't cover.
+//!
+//! A section names its template with the `template` attribute. Sections that name neither a
+//! template nor a `format` use the `template` of `.dashboard/config.json`, if any. A name is
+//! looked up in the inline `templates` of the config first, and read as a file path otherwise:
+//!
+//! ```md
+//! <!--START_SECTION:tag_1 sort=stars limit=5 template=starred-->
+//! <!--END_SECTION:tag_1-->
+//! <!--START_SECTION:tag_2 template=templates/table.md.j2-->
+//! <!--END_SECTION:tag_2-->
+//! ```
+//!
+//! ```json
+//! {
+//!   "template": "templates/list.md.j2",
+//!   "templates": {
+//!     "starred": "{% for repo in repos %}\n* {{ repo.name }} ★ {{ repo.stargazerCount }}\n{% endfor %}"
+//!   }
+//! }
+//! ```
+//!
+//! A template gets `repos`, the repositories of the section once filtered, sorted and limited.
+//! Each one has every field fetched from `gh repo list`, under its JSON name: `id`, `name`, `url`,
+//! `sshUrl`, `description`, `stargazerCount`, `diskUsage`, `repositoryTopics`, `createdAt`,
+//! `pushedAt` and `updatedAt`. This template renders the default list:
+//!
+//! ```jinja
+//! {% for repo in repos %}
+//! * [{{ repo.name }}]({{ repo.url }}){{ " — " ~ repo.description | truncate if repo.description else "" }}
+//! {% endfor %}
+//! ```
+//!
+//! On top of the builtin filters of [`minijinja`], templates can use:
+//!
+//! * `truncate(length=60, end="...")` - Cuts a string to `length` characters, followed by `end`.
+//! * `date(format="%Y-%m-%d")` - Formats a date of `gh` with `%Y`, `%m`, `%d`, `%H`, `%M`, `%S`,
+//!   `%b` (short month name) and `%%`.
+//! * `join_topics(sep=", ")` - Joins the names of `repositoryTopics`.
+//!
+//! Block tags don't leave blank lines behind, as the newline right after a tag is dropped, so
+//! conditions within a line are best written as `if` expressions. Using an undefined field is an
+//! error rather than an empty string, to catch typos.
+
+use minijinja::{context, Environment, Error, ErrorKind, UndefinedBehavior, Value};
+
+use crate::{app::AppError, config::Config, constant::DESC_WC, gh::GitRepo};
+
+/// Short month names, for `%b`.
+const MONTHS: [&str; 12] =
+    ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// Returns the source of the template `name`: the inline template of that name in `config`, or
+/// else the content of the file at path `name`.
+///
+/// # Errors
+///
+/// This function will return [`AppError::TemplateError`] if there is no such template.
+pub(crate) fn load(name: &str, config: &Config) -> Result<String, AppError> {
+    if let Some(source) = config.templates.get(name) {
+        return Ok(source.clone());
+    }
+    std::fs::read_to_string(name).map_err(|e| {
+        let message = format!("no template `{name}` in the config, and reading it failed: {e}");
+        AppError::TemplateError(Error::new(ErrorKind::TemplateNotFound, message))
+    })
+}
+
+/// Renders `source` with `repos`, see the [module documentation](self).
+///
+/// # Errors
+///
+/// This function will return [`AppError::TemplateError`] if the template has a syntax error, uses
+/// an undefined value, or a filter fails.
+pub(crate) fn render(source: &str, repos: &[&GitRepo]) -> Result<String, AppError> {
+    Ok(environment().render_str(source, context! { repos => repos })?)
+}
+
+/// Creates the environment templates are rendered in.
+fn environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.add_filter("truncate", truncate);
+    env.add_filter("date", date);
+    env.add_filter("join_topics", join_topics);
+
+    env
+}
+
+fn truncate(value: String, length: Option<usize>, end: Option<String>) -> String {
+    match value.char_indices().nth(length.unwrap_or(DESC_WC)) {
+        Some((i, _)) => format!("{}{}", &value[..i], end.as_deref().unwrap_or("...")),
+        None => value,
+    }
+}
+
+// Dates from `gh` are RFC 3339 in UTC, e.g. `2023-02-10T08:15:42Z`.
+fn date(value: String, format: Option<String>) -> Result<String, Error> {
+    let invalid = |reason: String| {
+        Error::new(ErrorKind::InvalidOperation, format!("cannot format date `{value}`: {reason}"))
+    };
+    let field = |start: usize, len: usize| {
+        value
+            .get(start..start + len)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))
+            .ok_or_else(|| invalid("expected a date like `2023-02-10T08:15:42Z`".to_string()))
+    };
+    if value.is_empty() {
+        return Ok(value);
+    }
+    let month = field(5, 2)?;
+    let month_name = month
+        .parse::<usize>()
+        .ok()
+        .and_then(|m| MONTHS.get(m.wrapping_sub(1)))
+        .ok_or_else(|| invalid(format!("no month {month}")))?;
+
+    let mut out = String::new();
+    let mut chars = format.as_deref().unwrap_or("%Y-%m-%d").chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(field(0, 4)?),
+            Some('m') => out.push_str(month),
+            Some('d') => out.push_str(field(8, 2)?),
+            Some('H') => out.push_str(field(11, 2)?),
+            Some('M') => out.push_str(field(14, 2)?),
+            Some('S') => out.push_str(field(17, 2)?),
+            Some('b') => out.push_str(month_name),
+            Some('%') => out.push('%'),
+            other => {
+                let spec = other.map_or(String::new(), String::from);
+                return Err(invalid(format!("unsupported format `%{spec}`")));
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+fn join_topics(value: Value, sep: Option<String>) -> Result<String, Error> {
+    if value.is_none() || value.is_undefined() {
+        return Ok(String::new());
+    }
+    let names = value
+        .try_iter()?
+        .map(|topic| match topic.get_attr("name") {
+            Ok(name) if !name.is_undefined() => name.to_string(),
+            _ => topic.to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    Ok(names.join(sep.as_deref().unwrap_or(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::findrepl::parse_attributes;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::{gh::RepositoryTopic, render::SectionOptions};
+
+    fn repo(name: &str, stars: u32, topics: &[&str]) -> GitRepo {
+        GitRepo {
+            created_at: "2023-02-10T08:15:42Z".to_string(),
+            description: format!("{name} does {}", "things ".repeat(stars as usize)),
+            disk_usage: 0,
+            id: name.to_string(),
+            name: name.to_string(),
+            pushed_at: String::new(),
+            repository_topics: match topics.is_empty() {
+                true => None,
+                false => {
+                    Some(topics.iter().map(|t| RepositoryTopic { name: t.to_string() }).collect())
+                }
+            },
+            ssh_url: String::new(),
+            stargazer_count: stars,
+            updated_at: String::new(),
+            url: format!("https://github.com/u/{name}"),
+        }
+    }
+
+    #[test]
+    fn should_render_sections_from_templates() {
+        let repos = vec![repo("a", 1, &["rust", "cli"]), repo("b", 9, &[])];
+        let mut config = Config::default();
+        config.templates.insert(
+            "starred".to_string(),
+            "{% for repo in repos %}\n\
+             * {{ repo.name }} ★ {{ repo.stargazerCount }} ({{ repo.createdAt | date('%b %Y') }})\n\
+             {% if repo.repositoryTopics %}\n  {{ repo.repositoryTopics | join_topics(' · ') }}\n{% endif %}\n\
+             {% endfor %}"
+                .to_string(),
+        );
+
+        let attrs = parse_attributes("sort=name template=starred").unwrap();
+        let options = SectionOptions::from_attributes(&attrs).unwrap();
+        assert_eq!(
+            options.render(&repos, &config).unwrap(),
+            "* a ★ 1 (Feb 2023)\n  rust · cli\n* b ★ 9 (Feb 2023)\n"
+        );
+
+        // The config template replaces the default list only.
+        config.template = Some("starred".to_string());
+        let table = SectionOptions::from_attributes(&parse_attributes("format=table").unwrap());
+        assert!(table.unwrap().render(&repos, &config).unwrap().starts_with("| Repository |"));
+        let list = SectionOptions::default().render(&repos, &config).unwrap();
+        assert!(list.starts_with("* a ★ 1"), "{list}");
+
+        let list = "{% for repo in repos %}\n\
+                    * [{{ repo.name }}]({{ repo.url }})\
+                    {{ ' — ' ~ repo.description | truncate if repo.description else '' }}\n\
+                    {% endfor %}";
+        assert_eq!(
+            render(list, &repos.iter().collect::<Vec<_>>()).unwrap().trim_end(),
+            SectionOptions::default().render(&repos, &Config::default()).unwrap()
+        );
+
+        let config = Config { template: Some("missing.md.j2".to_string()), ..Config::default() };
+        let err = SectionOptions::default().render(&repos, &config).unwrap_err();
+        assert!(matches!(err, AppError::TemplateError(_)), "{err:?}");
+    }
+
+    #[test]
+    fn should_apply_filters() {
+        assert_eq!(truncate("héllo wörld".to_string(), Some(5), None), "héllo...");
+        assert_eq!(truncate("short".to_string(), None, Some("…".to_string())), "short");
+        assert_eq!(
+            date("2023-02-10T08:15:42Z".to_string(), Some("%d %b %Y, %H:%M:%S %%".to_string()))
+                .unwrap(),
+            "10 Feb 2023, 08:15:42 %"
+        );
+        assert_eq!(date(String::new(), None).unwrap(), "");
+        assert!(date("yesterday".to_string(), None).is_err());
+        assert!(date("2023-02-10T08:15:42Z".to_string(), Some("%q".to_string())).is_err());
+
+        let env = environment();
+        let render = |source: &str| env.render_str(source, context! { topics => vec!["a", "b"] });
+        assert_eq!(render("{{ topics | join_topics }}").unwrap(), "a, b");
+        assert_eq!(render("{{ none | join_topics }}").unwrap(), "");
+        assert!(render("{{ repo.stars }}").is_err());
+    }
+}
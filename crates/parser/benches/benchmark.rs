@@ -1 +1,98 @@
+//! `benchmark`
+//!
+//! Compares replacing a section of a large document held in memory with [`replace_in_str`], which
+//! builds the whole new document, with streaming it line by line through [`replace_streaming`].
+//!
+//! Criterion only measures time, so the allocations of each approach are tracked by a counting
+//! global allocator, and streaming is asserted to peak lower before the timings.
+//!
+//! [`replace_in_str`]: parser::findrepl::replace_in_str
+//! [`replace_streaming`]: parser::findrepl::replace_streaming
 
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    io,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use parser::findrepl::{replace_in_str, replace_streaming, CommentBlock};
+
+/// `CountingAlloc` forwards to the system allocator, keeping track of the bytes in use and of
+/// their peak.
+struct CountingAlloc;
+
+static IN_USE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let in_use = IN_USE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(in_use, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        IN_USE.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Lines of the document around the section, and lines in the section.
+const LINES: usize = 20_000;
+const SECTION_LINES: usize = 1_000;
+
+/// Returns a markdown document of `2 * LINES` lines with the section `tag_1` in the middle.
+fn document() -> String {
+    let line = |i| format!("* [repo-{i}](https://github.com/username/repo-{i}) — Lorem ipsum.\n");
+    let mut doc = String::new();
+    (0..LINES).for_each(|i| doc.push_str(&line(i)));
+    doc.push_str("<!--START_SECTION:tag_1-->\n");
+    (0..SECTION_LINES).for_each(|i| doc.push_str(&line(i)));
+    doc.push_str("<!--END_SECTION:tag_1-->\n");
+    (0..LINES).for_each(|i| doc.push_str(&line(i)));
+
+    doc
+}
+
+/// Returns the peak of bytes allocated while running `f`, on top of those already in use.
+fn peak_bytes(f: impl FnOnce()) -> usize {
+    let before = IN_USE.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    f();
+    PEAK.load(Ordering::Relaxed) - before
+}
+
+fn bench_replace(c: &mut Criterion) {
+    let (doc, block) = (document(), CommentBlock::new("tag_1".to_string()));
+    let text = "* [new](https://github.com/username/new)";
+
+    let in_memory = peak_bytes(|| {
+        replace_in_str(text, &block, &doc).unwrap();
+    });
+    let streaming = peak_bytes(|| {
+        replace_streaming(text, &block, doc.as_bytes(), io::sink()).unwrap();
+    });
+    assert!(
+        streaming < in_memory,
+        "streaming should peak lower than `replace_in_str`: {streaming} >= {in_memory} bytes"
+    );
+
+    let mut group = c.benchmark_group("replace_large_document");
+    group.bench_function("replace_in_str", |b| {
+        b.iter(|| replace_in_str(black_box(text), &block, black_box(&doc)))
+    });
+    group.bench_function("replace_streaming", |b| {
+        b.iter(|| replace_streaming(black_box(text), &block, black_box(doc.as_bytes()), io::sink()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_replace);
+criterion_main!(benches);
@@ -0,0 +1,151 @@
+//! `attrs` is a Rust module for the attributes written after a section name in a marker.
+//!
+//! A section can say what it should contain right where it lives:
+//!
+//! ```md
+//! <!--START_SECTION:repos sort=stars limit=10 title="Top projects"-->
+//! <!--END_SECTION:repos-->
+//! ```
+//!
+//! Attributes are `key=value` or `key="quoted value"` pairs separated by whitespace. Inside quotes,
+//! `\"` and `\\` escape a quote and a backslash.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use parser::findrepl::parse_attributes;
+//!
+//! let attrs = parse_attributes(r#"sort=stars title="Top projects""#).unwrap();
+//! assert_eq!(attrs["sort"], "stars");
+//! assert_eq!(attrs["title"], "Top projects");
+//! ```
+
+use std::collections::BTreeMap;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::error::ParserError;
+
+/// `Attributes` maps each attribute key of a marker to its unquoted value.
+pub type Attributes = BTreeMap<String, String>;
+
+lazy_static! {
+    /// Matches the whitespace and key that start the first attribute after a section name.
+    static ref RE_FIRST_KEY: Regex = Regex::new(r"\s+[A-Za-z_][\w.-]*=").unwrap();
+}
+
+/// Splits the text after `START_SECTION:` into the section name and its raw attributes.
+///
+/// The name runs up to the first ` key=` token, so names may contain spaces as long as no word
+/// of them looks like an attribute.
+pub(crate) fn split_name(rest: &str) -> (&str, &str) {
+    let rest = rest.trim();
+    match RE_FIRST_KEY.find(rest) {
+        Some(m) => (rest[..m.start()].trim_end(), rest[m.start()..].trim()),
+        None => (rest, ""),
+    }
+}
+
+/// `parse_attributes` parses whitespace-separated `key=value` and `key="quoted value"` pairs.
+///
+/// # Errors
+///
+/// This function will return [`ParserError::InvalidAttribute`] if a pair has no `=`, an empty
+/// or invalid key, an unterminated quote, or if a key is repeated.
+pub fn parse_attributes(raw: &str) -> crate::Result<Attributes> {
+    let invalid = |reason: &str| ParserError::InvalidAttribute(format!("{reason} in `{raw}`"));
+    let mut attrs = Attributes::new();
+    let mut chars = raw.trim().chars().peekable();
+
+    while chars.peek().is_some() {
+        let (mut key, mut has_value) = (String::new(), false);
+        for c in chars.by_ref() {
+            if c == '=' {
+                has_value = true;
+                break;
+            }
+            key.push(c);
+        }
+        if !has_value || key.is_empty() || key.chars().any(char::is_whitespace) {
+            return Err(invalid("expected `key=value`"));
+        }
+        if !key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            return Err(invalid(&format!("invalid key `{key}`")));
+        }
+
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => value.extend(chars.next()),
+                    Some(c) => value.push(c),
+                    None => return Err(invalid(&format!("unterminated quote for `{key}`"))),
+                }
+            }
+            if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+                return Err(invalid(&format!("expected whitespace after `{key}`")));
+            }
+        } else {
+            value = chars.by_ref().take_while(|c| !c.is_whitespace()).collect();
+        }
+
+        if attrs.insert(key.clone(), value).is_some() {
+            return Err(invalid(&format!("duplicate key `{key}`")));
+        }
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+    }
+
+    Ok(attrs)
+}
+
+/// `fmt_attributes` writes `attrs` back as marker attributes, quoting values when needed.
+pub fn fmt_attributes(attrs: &Attributes) -> String {
+    attrs
+        .iter()
+        .map(|(key, value)| {
+            let bare = !value.is_empty()
+                && !value.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\');
+            match bare {
+                true => format!("{key}={value}"),
+                false => format!("{key}=\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_split_name_from_attributes() {
+        assert_eq!(split_name("repos sort=stars limit=10"), ("repos", "sort=stars limit=10"));
+        assert_eq!(split_name(" tag_1 "), ("tag_1", ""));
+        assert_eq!(split_name("v1.0 (beta)"), ("v1.0 (beta)", ""));
+    }
+
+    #[test]
+    fn should_parse_and_format_attributes() {
+        let raw = r#"sort=stars limit=10 title="Top \"Rust\" projects""#;
+        let attrs = parse_attributes(raw).unwrap();
+        assert_eq!(attrs.len(), 3);
+        assert_eq!(attrs["title"], r#"Top "Rust" projects"#);
+        assert_eq!(parse_attributes(&fmt_attributes(&attrs)).unwrap(), attrs);
+        assert_eq!(parse_attributes("").unwrap(), Attributes::new());
+    }
+
+    #[test]
+    fn should_reject_malformed_attributes() {
+        for raw in ["sort", "sort=a sort=b", r#"title="open"#, "=x", "1a=b", r#"a="b"c"#] {
+            assert!(parse_attributes(raw).is_err(), "{raw}");
+        }
+    }
+}
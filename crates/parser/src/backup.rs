@@ -0,0 +1,296 @@
+//! `backup` is a Rust module for keeping the previous versions of the files a section is written
+//! to, so a bad render can be undone without digging through git.
+//!
+//! [`Backups`] copies a file into its backup directory right before it is overwritten, e.g.
+//! `README.md` to `.dashboard/backups/README.md.20261017T093000.123Z`, and keeps only the most
+//! recent copies of each file.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::path::Path;
+//!
+//! use parser::findrepl::{replace_with_backups, Backups, CommentBlock};
+//!
+//! let backups = Backups::new(".dashboard/backups", 5);
+//! let block = CommentBlock::new("tag_1".to_string());
+//! replace_with_backups("* new", block, Path::new("README.md"), &backups).unwrap();
+//!
+//! let latest = backups.list(Path::new("README.md")).unwrap().pop().unwrap();
+//! backups.restore(Path::new("README.md"), &latest).unwrap();
+//! ```
+
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::error::ParserError;
+
+/// `Backups` keeps up to `keep` rotated copies of each file in `dir`, mirroring the relative path
+/// of the file under it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backups {
+    dir: PathBuf,
+    keep: usize,
+}
+
+/// `Backup` is one saved version of a file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Backup {
+    /// When the version was saved, as a UTC timestamp like `20261017T093000.123Z`. Ids sort in
+    /// the order the backups were taken.
+    pub id: String,
+    /// Where the copy is stored.
+    pub path: PathBuf,
+}
+
+impl Backups {
+    /// Creates a [`Backups`] storing up to `keep` copies of each file in `dir`. Nothing is saved
+    /// if `keep` is 0.
+    pub fn new(dir: impl Into<PathBuf>, keep: usize) -> Self {
+        Self { dir: dir.into(), keep }
+    }
+
+    /// Returns the directory the copies are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// `save` copies the file at `path` into the backup directory, then removes its oldest copies
+    /// beyond the number to keep.
+    ///
+    /// Returns the new backup, or `None` if backups are disabled.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file can't be copied or old copies can't be
+    /// removed.
+    pub fn save(&self, path: &Path) -> super::Result<Option<Backup>> {
+        if self.keep == 0 {
+            return Ok(None);
+        }
+        let stem = self.stem(path);
+        fs::create_dir_all(stem.parent().unwrap_or(&self.dir))?;
+
+        // Ids must sort after the latest one, even when saves come within the same millisecond.
+        let latest = self.list(path)?.pop().map(|backup| backup.id).unwrap_or_default();
+        let mut time = SystemTime::now();
+        let backup = loop {
+            let id = timestamp(time);
+            if id > latest {
+                break Backup { path: with_id(&stem, &id), id };
+            }
+            time += Duration::from_millis(1);
+        };
+        fs::copy(path, &backup.path)?;
+        log::debug!("Backed up {} to {}", path.display(), backup.path.display());
+
+        let backups = self.list(path)?;
+        for old in &backups[..backups.len().saturating_sub(self.keep)] {
+            fs::remove_file(&old.path)?;
+        }
+
+        Ok(Some(backup))
+    }
+
+    /// `list` returns the backups of the file at `path`, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the backup directory exists but can't be read.
+    pub fn list(&self, path: &Path) -> super::Result<Vec<Backup>> {
+        let stem = self.stem(path);
+        let (Some(dir), Some(name)) = (stem.parent(), stem.file_name()) else {
+            return Ok(Vec::new());
+        };
+        let prefix = format!("{}.", name.to_string_lossy());
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut backups = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            match file_name.strip_prefix(&prefix) {
+                Some(id) if is_timestamp(id) => {
+                    backups.push(Backup { id: id.to_string(), path: entry.path() })
+                }
+                _ => (),
+            }
+        }
+        backups.sort();
+
+        Ok(backups)
+    }
+
+    /// `find` returns the backup of the file at `path` with the id `id`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`ParserError::BackupNotFound`] if there is no such backup.
+    pub fn find(&self, path: &Path, id: &str) -> super::Result<Backup> {
+        self.list(path)?.into_iter().find(|backup| backup.id == id).ok_or_else(|| {
+            ParserError::BackupNotFound { id: id.to_string(), path: path.to_path_buf() }
+        })
+    }
+
+    /// `restore` puts the version saved in `backup` back at `path`, after backing up the current
+    /// one, so a restore can be undone too. Both happen under the lock of the file, like an
+    /// update.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the backup can't be read or the file can't be
+    /// written.
+    pub fn restore(&self, path: &Path, backup: &Backup) -> super::Result<()> {
+        let contents = fs::read(&backup.path)?;
+        crate::findrepl::write_atomic(path, &contents, Some(self))?;
+        log::info!("Restored {} from backup {}", path.display(), backup.id);
+
+        Ok(())
+    }
+
+    /// Returns where the copies of `path` are stored, without their id. Files in the working
+    /// directory mirror their relative path, others are stored by name with a hash of their
+    /// absolute path, so `../README.md` and `README.md` don't share copies.
+    fn stem(&self, path: &Path) -> PathBuf {
+        let cwd = normalize(&std::env::current_dir().unwrap_or_default());
+        let absolute = normalize(&cwd.join(path));
+        match absolute.strip_prefix(&cwd) {
+            Ok(relative) => self.dir.join(relative),
+            Err(_) => {
+                let name = absolute.file_name().unwrap_or_default().to_string_lossy();
+                self.dir.join(format!("{name}-{:016x}", path_hash(&absolute)))
+            }
+        }
+    }
+}
+
+/// Returns `path` with its `.` and `..` components resolved, without following symlinks.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+/// Returns the 64-bit FNV-1a hash of `path`, which is stable across runs and toolchains.
+pub(crate) fn path_hash(path: &Path) -> u64 {
+    path.to_string_lossy().bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Returns `stem` with `.id` appended to its file name.
+fn with_id(stem: &Path, id: &str) -> PathBuf {
+    let mut name = stem.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{id}"));
+    stem.with_file_name(name)
+}
+
+/// Formats `time` as a UTC timestamp like `20261017T093000.123Z`.
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from the number of days since 1970-01-01, after Howard Hinnant's algorithm.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}.{:03}Z",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Returns `true` if `id` is shaped like the timestamps of [`timestamp`].
+fn is_timestamp(id: &str) -> bool {
+    id.len() == 20
+        && id.char_indices().all(|(i, c)| match i {
+            8 => c == 'T',
+            15 => c == '.',
+            19 => c == 'Z',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn should_format_timestamps() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+        assert_eq!(timestamp(time), "20240229T123456.789Z");
+        assert_eq!(timestamp(UNIX_EPOCH), "19700101T000000.000Z");
+        assert!(is_timestamp(&timestamp(SystemTime::now())));
+    }
+
+    #[test]
+    fn should_rotate_and_restore_backups() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("docs/README.md");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let backups = Backups::new(dir.path().join("backups"), 2);
+
+        for version in ["v1", "v2", "v3"] {
+            fs::write(&path, version).unwrap();
+            backups.save(&path).unwrap();
+        }
+        let saved = backups.list(&path).unwrap();
+        let contents: Vec<_> = saved.iter().map(|b| fs::read_to_string(&b.path).unwrap()).collect();
+        assert_eq!(contents, ["v2", "v3"]);
+        assert!(saved[0].path.starts_with(backups.dir()));
+        let name = format!("README.md-{:016x}.", path_hash(&path));
+        assert!(saved[0].path.file_name().unwrap().to_string_lossy().starts_with(&name));
+
+        fs::write(&path, "broken").unwrap();
+        backups.restore(&path, &backups.find(&path, &saved[0].id).unwrap()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "v2");
+        let latest = backups.list(&path).unwrap().pop().unwrap();
+        assert_eq!(fs::read_to_string(latest.path).unwrap(), "broken");
+
+        let err = backups.find(&path, "20000101T000000.000Z").unwrap_err();
+        assert!(matches!(err, ParserError::BackupNotFound { .. }), "{err:?}");
+        assert_eq!(Backups::new(backups.dir(), 0).save(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn should_keep_copies_of_distinct_paths_apart() {
+        let backups = Backups::new(".dashboard/backups", 5);
+        let stem = |path: &str| backups.stem(Path::new(path));
+
+        assert_eq!(stem("docs/README.md"), Path::new(".dashboard/backups/docs/README.md"));
+        assert_eq!(stem("./docs/../README.md"), stem("README.md"));
+        assert_ne!(stem("../README.md"), stem("README.md"));
+        assert_ne!(stem("../README.md"), stem("../../README.md"));
+        assert_ne!(stem("/README.md"), stem("README.md"));
+    }
+}
@@ -0,0 +1,289 @@
+//! `batch` is a Rust module for updating a section in many files at once.
+//!
+//! [`expand_globs`] turns glob patterns into the list of files they match, and
+//! [`replace_in_files`] updates the section of each file in parallel. A file that fails doesn't
+//! stop the others: every file gets a [`FileReport`] telling whether it was updated, left
+//! unchanged, had the section created, was skipped for lack of markers, or failed. Only files
+//! matched by a pattern are skipped for lack of markers; files named as is fail instead.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use parser::findrepl::{expand_globs, replace_in_files, CommentBlock};
+//!
+//! let paths = expand_globs(&["README.md", "crates/*/README.md", "docs/**/*.md"]).unwrap();
+//! let block = CommentBlock::new("tag_1".to_string());
+//! for report in replace_in_files(&paths, &block, None, |_path, _attrs| Ok("* new".to_string())) {
+//!     println!("{report}");
+//! }
+//! ```
+
+use std::{
+    collections::HashSet,
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use rayon::prelude::*;
+
+use crate::{
+    error::ParserError,
+    findrepl::{
+        has_markers, read_file, replace_file_with, section_attributes_in_str, Attributes, Backups,
+        CommentBlock,
+    },
+};
+
+/// `FileStatus` is the outcome of updating the section of one file.
+#[derive(Debug)]
+pub enum FileStatus {
+    /// The section was rewritten.
+    Updated,
+    /// The file had no markers of the section, so they were created as asked by the
+    /// [`Insert`](crate::findrepl::Insert) policy of the block, then filled.
+    Created,
+    /// The section was already up to date, so the file was left untouched.
+    Unchanged,
+    /// The file has no markers of the section and was skipped.
+    NoMarkers,
+    /// The file couldn't be updated.
+    Error(ParserError),
+}
+
+impl FileStatus {
+    /// Returns `true` if the file status is [`FileStatus::Error`].
+    #[must_use]
+    pub fn is_error(&self) -> bool {
+        matches!(self, Self::Error(..))
+    }
+}
+
+impl Display for FileStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Updated => write!(f, "updated"),
+            Self::Created => write!(f, "created"),
+            Self::Unchanged => write!(f, "unchanged"),
+            Self::NoMarkers => write!(f, "no markers"),
+            Self::Error(_) => write!(f, "error"),
+        }
+    }
+}
+
+/// `BatchFile` is a file of a batch update, as found by [`expand_globs`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchFile {
+    /// The file.
+    pub path: PathBuf,
+    /// Whether the file was matched by a glob pattern rather than named as is, in which case it
+    /// is skipped if it has no markers of the section.
+    pub matched: bool,
+}
+
+/// `FileReport` tells what happened to one file of a batch update.
+#[derive(Debug)]
+pub struct FileReport {
+    /// The file.
+    pub path: PathBuf,
+    /// What happened to it.
+    pub status: FileStatus,
+}
+
+impl Display for FileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>10}  {}", self.status.to_string(), self.path.display())?;
+        match &self.status {
+            FileStatus::Error(e) => write!(f, ": {}", e.message_without_path()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// `is_glob` returns `true` if `pattern` has any of the `*`, `?` or `[` wildcards of a glob
+/// pattern, rather than naming a single file.
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// `expand_globs` returns the files matched by `patterns`, such as `docs/**/*.md`, in the order
+/// of the patterns and without duplicates. Directories are left out, and a pattern matching no
+/// file is only logged. A pattern without wildcards names its file as is, which is kept even if
+/// it doesn't exist, so updating it reports the error.
+///
+/// # Errors
+///
+/// This function will return [`ParserError::InvalidGlob`] if a pattern is malformed, or an I/O
+/// error if a directory it walks can't be read.
+pub fn expand_globs<S: AsRef<str>>(patterns: &[S]) -> super::Result<Vec<BatchFile>> {
+    let mut seen = HashSet::new();
+    let mut paths = Vec::new();
+    for pattern in patterns.iter().map(AsRef::as_ref) {
+        if !is_glob(pattern) {
+            let path = PathBuf::from(pattern);
+            match seen.insert(path.clone()) {
+                true => paths.push(BatchFile { path, matched: false }),
+                // A file matched by an earlier pattern is named as is too.
+                false => {
+                    paths.iter_mut().filter(|f| f.path == path).for_each(|f| f.matched = false)
+                }
+            }
+            continue;
+        }
+        let entries = glob::glob(pattern).map_err(|e| ParserError::InvalidGlob {
+            pattern: pattern.to_string(),
+            reason: e.msg.to_string(),
+        })?;
+        let mut matched = false;
+        for entry in entries {
+            let path = entry.map_err(|e| ParserError::Io(Arc::new(e.into_error())))?;
+            if path.is_file() {
+                matched = true;
+                if seen.insert(path.clone()) {
+                    paths.push(BatchFile { path, matched: true });
+                }
+            }
+        }
+        if !matched {
+            log::warn!("No file matches `{pattern}`");
+        }
+    }
+
+    Ok(paths)
+}
+
+/// `replace_in_files` updates the section of `block` in each of `files` in parallel, with the
+/// text returned by `render` for the file and the attributes of its start marker.
+///
+/// Each file is handled like [`replace`](crate::findrepl::replace), with the comment style picked
+/// from its extension, and saved in `backups` first if it changes. A file without markers is
+/// reported as [`FileStatus::NoMarkers`] if it was matched by a pattern, or else as the
+/// [`ParserError::MarkerNotFound`] error. Reports are returned in the order of `files`.
+pub fn replace_in_files<F>(
+    files: &[BatchFile],
+    block: &CommentBlock,
+    backups: Option<&Backups>,
+    render: F,
+) -> Vec<FileReport>
+where
+    F: Fn(&Path, &Attributes) -> super::Result<String> + Sync,
+{
+    files
+        .par_iter()
+        .map(|file| FileReport {
+            path: file.path.clone(),
+            status: replace_one(file, block, backups, &render),
+        })
+        .collect()
+}
+
+fn replace_one<F>(
+    file: &BatchFile,
+    block: &CommentBlock,
+    backups: Option<&Backups>,
+    render: &F,
+) -> FileStatus
+where
+    F: Fn(&Path, &Attributes) -> super::Result<String>,
+{
+    let path = file.path.as_path();
+    let block = block.clone().for_path(path);
+    // The attributes are read under the lock of the update, so they match the markers replaced.
+    let result = replace_file_with(&block, path, backups, || {
+        section_attributes_in_str(&block, &read_file(path)?).and_then(|attrs| render(path, &attrs))
+    });
+
+    match result {
+        Ok(status) => status,
+        Err(e @ ParserError::MarkerNotFound { .. }) if file.matched => {
+            match read_file(path).and_then(|buf| has_markers(&buf, &block)) {
+                Ok(false) => FileStatus::NoMarkers,
+                _ => FileStatus::Error(e),
+            }
+        }
+        Err(e) => FileStatus::Error(e.with_path(path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn should_update_files_matching_globs_and_report_each() {
+        let dir = tempdir().unwrap();
+        let section = "<!--START_SECTION:tag_1-->\nold\n<!--END_SECTION:tag_1-->\n";
+        fs::create_dir_all(dir.path().join("docs/guide")).unwrap();
+        fs::write(dir.path().join("README.md"), section).unwrap();
+        fs::write(dir.path().join("docs/guide/a.md"), section.replace("old", "new")).unwrap();
+        fs::write(dir.path().join("docs/b.md"), "```\n<!--START_SECTION:tag_1-->\n```\n").unwrap();
+        fs::write(dir.path().join("docs/c.md"), "<!--START_SECTION:tag_1-->\n").unwrap();
+
+        let root = dir.path().display();
+        let patterns =
+            [format!("{root}/*.md"), format!("{root}/docs/**/*.md"), format!("{root}/*")];
+        let paths = expand_globs(&patterns).unwrap();
+        let names: Vec<_> =
+            paths.iter().map(|f| f.path.strip_prefix(dir.path()).unwrap().to_path_buf()).collect();
+        let expected = ["README.md", "docs/b.md", "docs/c.md", "docs/guide/a.md"];
+        assert_eq!(names, expected.map(PathBuf::from));
+
+        let block = CommentBlock::new("tag_1".to_string());
+        let reports = replace_in_files(&paths, &block, None, |_, _| Ok("new".to_string()));
+        let statuses: Vec<String> = reports.iter().map(|r| r.status.to_string()).collect();
+        assert_eq!(statuses, ["updated", "no markers", "error", "unchanged"]);
+        assert_eq!(fs::read_to_string(&paths[0].path).unwrap(), section.replace("old", "new"));
+        assert!(reports[2]
+            .to_string()
+            .ends_with("c.md: Marker `<!--END_SECTION:tag_1-->` not found"));
+
+        // Files named as is fail without markers, even when a pattern matches them too.
+        let patterns = [format!("{root}/docs/*.md"), format!("{root}/docs/b.md")];
+        let paths = expand_globs(&patterns).unwrap();
+        assert_eq!(paths.iter().map(|f| f.matched).collect::<Vec<_>>(), [false, true]);
+        let reports = replace_in_files(&paths, &block, None, |_, _| Ok("new".to_string()));
+        assert!(matches!(reports[0].status, FileStatus::Error(ParserError::MarkerNotFound { .. })));
+        let missing = expand_globs(&[format!("{root}/missing.md")]).unwrap();
+        let reports = replace_in_files(&missing, &block, None, |_, _| Ok("new".to_string()));
+        assert!(reports[0].status.is_error());
+
+        let err = expand_globs(&["docs/***"]).unwrap_err();
+        assert!(matches!(err, ParserError::InvalidGlob { .. }), "{err:?}");
+    }
+
+    #[test]
+    fn should_render_with_the_attributes_read_under_the_lock() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("README.md");
+        let section = "<!--START_SECTION:tag_1 sort=stars-->\nold\n<!--END_SECTION:tag_1-->\n";
+        fs::write(&path, section).unwrap();
+
+        let block = CommentBlock::new("tag_1".to_string());
+        let paths = [BatchFile { path: path.clone(), matched: false }];
+        let reports = replace_in_files(&paths, &block, None, |_, attrs| {
+            Ok(attrs.get("sort").cloned().unwrap_or_default())
+        });
+        assert_eq!(reports[0].status.to_string(), "updated");
+        assert_eq!(fs::read_to_string(&path).unwrap(), section.replace("old", "stars"));
+
+        // An edit made while rendering, after the attributes were read, isn't overwritten.
+        let reports = replace_in_files(&paths, &block, None, |path, _| {
+            fs::write(path, section.replace("stars", "name")).unwrap();
+            Ok("new".to_string())
+        });
+        assert!(
+            matches!(
+                reports[0].status,
+                FileStatus::Error(ParserError::ConcurrentModification { .. })
+            ),
+            "{}",
+            reports[0]
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), section.replace("stars", "name"));
+    }
+}
@@ -0,0 +1,191 @@
+//! `diff` is a Rust module for line-level diffs between two versions of a document.
+//!
+//! It defines the following items:
+//!
+//! * [`Diff`] - the hunks that turn the old document into the new one.
+//! * [`Hunk`] - a run of changed lines together with some unchanged context around them.
+//! * [`DiffLine`] and [`DiffTag`] - a single line of a hunk and whether it was kept, removed or
+//!   added.
+//!
+//! A [`Diff`] displays as a plain unified diff. See [`crate::printer`] for the colored version.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use parser::diff::Diff;
+//!
+//! let diff = Diff::lines("a\nb\nc\n", "a\nB\nc\n");
+//! assert_eq!(diff.to_string(), "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n");
+//! ```
+
+use std::fmt::{self, Display};
+
+use similar::{ChangeTag, TextDiff};
+
+/// Number of unchanged lines shown around each change.
+pub const CONTEXT_LINES: usize = 3;
+
+/// `DiffTag` tells whether a [`DiffLine`] was kept, removed or added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffTag {
+    /// The line is the same in both documents.
+    Equal,
+    /// The line only exists in the old document.
+    Delete,
+    /// The line only exists in the new document.
+    Insert,
+}
+
+impl DiffTag {
+    /// Returns the unified diff prefix of the tag: `' '`, `'-'` or `'+'`.
+    pub fn sign(&self) -> char {
+        match self {
+            DiffTag::Equal => ' ',
+            DiffTag::Delete => '-',
+            DiffTag::Insert => '+',
+        }
+    }
+}
+
+/// `DiffLine` is a single line of a [`Hunk`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    /// Whether the line was kept, removed or added.
+    pub tag: DiffTag,
+    /// 1-based line number in the old document, if the line exists there.
+    pub old_line: Option<usize>,
+    /// 1-based line number in the new document, if the line exists there.
+    pub new_line: Option<usize>,
+    /// Content of the line without its line ending.
+    pub content: String,
+}
+
+/// `Hunk` is a group of nearby changes with their surrounding context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// 1-based first line of the hunk in the old document.
+    pub old_start: usize,
+    /// Number of lines the hunk covers in the old document.
+    pub old_len: usize,
+    /// 1-based first line of the hunk in the new document.
+    pub new_start: usize,
+    /// Number of lines the hunk covers in the new document.
+    pub new_len: usize,
+    /// Lines of the hunk in order.
+    pub lines: Vec<DiffLine>,
+}
+
+impl Hunk {
+    /// Returns the `@@ -a,b +c,d @@` header of the hunk.
+    pub fn header(&self) -> String {
+        format!("@@ -{},{} +{},{} @@", self.old_start, self.old_len, self.new_start, self.new_len)
+    }
+}
+
+/// `Diff` is a line-level diff between two documents, grouped into [`Hunk`]s.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Diff {
+    /// Hunks in document order. Empty if both documents are equal.
+    pub hunks: Vec<Hunk>,
+}
+
+impl Diff {
+    /// Computes the line-level diff from `old` to `new` with [`CONTEXT_LINES`] of context.
+    pub fn lines(old: &str, new: &str) -> Self {
+        let text_diff = TextDiff::from_lines(old, new);
+        let hunks = text_diff
+            .grouped_ops(CONTEXT_LINES)
+            .iter()
+            .filter_map(|group| {
+                let (first, last) = (group.first()?, group.last()?);
+                let (old_range, new_range) = (
+                    first.old_range().start..last.old_range().end,
+                    first.new_range().start..last.new_range().end,
+                );
+                let lines = group
+                    .iter()
+                    .flat_map(|op| text_diff.iter_changes(op))
+                    .map(|change| DiffLine {
+                        tag: match change.tag() {
+                            ChangeTag::Equal => DiffTag::Equal,
+                            ChangeTag::Delete => DiffTag::Delete,
+                            ChangeTag::Insert => DiffTag::Insert,
+                        },
+                        old_line: change.old_index().map(|i| i + 1),
+                        new_line: change.new_index().map(|i| i + 1),
+                        content: strip_line_ending(change.value()).to_string(),
+                    })
+                    .collect();
+                Some(Hunk {
+                    old_start: old_range.start + 1,
+                    old_len: old_range.len(),
+                    new_start: new_range.start + 1,
+                    new_len: new_range.len(),
+                    lines,
+                })
+            })
+            .collect();
+
+        Self { hunks }
+    }
+
+    /// Returns `true` if the documents are equal.
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    /// Returns the number of `(inserted, deleted)` lines.
+    pub fn stats(&self) -> (usize, usize) {
+        self.hunks.iter().flat_map(|h| &h.lines).fold((0, 0), |(ins, del), line| match line.tag {
+            DiffTag::Insert => (ins + 1, del),
+            DiffTag::Delete => (ins, del + 1),
+            DiffTag::Equal => (ins, del),
+        })
+    }
+}
+
+impl Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for hunk in &self.hunks {
+            writeln!(f, "{}", hunk.header())?;
+            for line in &hunk.lines {
+                writeln!(f, "{}{}", line.tag.sign(), line.content)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_be_empty_for_equal_documents() {
+        let diff = Diff::lines("a\nb\n", "a\nb\n");
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "");
+    }
+
+    #[test]
+    fn should_number_lines_and_count_changes() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let new = "1\n2\n3\n4\n5\n6\n7\nEIGHT\n9\nten\n";
+        let diff = Diff::lines(old, new);
+
+        assert_eq!(diff.hunks.len(), 1);
+        let hunk = &diff.hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_len, hunk.new_start, hunk.new_len), (5, 5, 5, 6));
+        let deleted = hunk.lines.iter().find(|l| l.tag == DiffTag::Delete).unwrap();
+        assert_eq!((deleted.old_line, deleted.new_line), (Some(8), None));
+        assert_eq!(deleted.content, "8");
+        assert_eq!(diff.stats(), (2, 1));
+    }
+}
@@ -4,7 +4,9 @@
 //!
 //! * The `ParserError` enum represents errors that may occur during parsing and has variants such
 //!   as: `Io` for I/O errors, `LogicBug` for logic errors, `RegexError` for regex errors, and
-//!   `PrinterError` and `AnyhowError` for errors from other libraries.
+//!   `PrinterError` and `AnyhowError` for errors from other libraries. Malformed sections are
+//!   reported with the [`Span`] of the markers involved, e.g. `MarkerNotFound`,
+//!   `MarkersOutOfOrder` and `DuplicateSection`.
 //! * The `PrinterError` enum represents errors that may occur during printing and has variants such
 //!   as `TermcolorError`, `Io`, `BufferError`, and `InvalidColor`.
 //! * The `ErrorColor` enum represents different colors that can be used.
@@ -41,14 +43,50 @@
 // #[cfg(doctest)]
 // doctest!("../README.md");
 
-use std::{convert::Into, io, sync::Arc, write};
+use std::{
+    convert::Into,
+    fmt::{self, Display},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    write,
+};
 
 use regex::Error as RegexError;
 
+use crate::{diff::Diff, scan::SectionProblem};
+
 /// `Result<T, E>` is an alias for `anyhow::Result` with [`ParserError`] as the error type.
 /// It is used as the return type for functions that may fail and return an error.
 pub type Result<T> = anyhow::Result<T, ParserError>;
 
+/// `Span` locates a marker in a document.
+///
+/// Lines and columns are 0-based and counted in characters. A span displays as the 1-based
+/// `line:column` shown by editors.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    /// Line of the marker.
+    pub line: usize,
+    /// Column of the first character of the marker.
+    pub column: usize,
+    /// Length of the marker in characters.
+    pub len: usize,
+}
+
+impl Span {
+    /// Creates the [`Span`] of the bytes `range` of `text`, found on line `line`.
+    pub fn new(line: usize, text: &str, range: std::ops::Range<usize>) -> Self {
+        Self { line, column: text[..range.start].chars().count(), len: text[range].chars().count() }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line + 1, self.column + 1)
+    }
+}
+
 /// The `ParserError` enum represents the different errors that can occur while parsing some input.
 #[derive(Debug, thiserror::Error)]
 pub enum ParserError {
@@ -66,7 +104,7 @@ pub enum ParserError {
     #[error("Regex error")]
     RegexError(#[from] regex::Error),
     /// An error occurred in the printer
-    #[error("Printer error")]
+    #[error("Printer error: {0}")]
     PrinterError(#[from] PrinterError),
     /// An error occurred using the anyhow library
     #[error("Anyhow error")]
@@ -74,6 +112,154 @@ pub enum ParserError {
     /// An error occurred using the crossbeam library
     #[error("Crossbeam scope return result error: {0}")]
     ChannelError(String),
+    /// A start or end marker of a section is missing
+    #[error("Marker `{marker}` not found{}", in_path(path))]
+    MarkerNotFound {
+        /// Name of the section.
+        section: String,
+        /// The marker that was looked for.
+        marker: String,
+        /// File that was searched, if any.
+        path: Option<PathBuf>,
+    },
+    /// The end marker of a section comes before its start marker
+    #[error(
+        "End marker of section `{section}` at {end} comes before its start marker at {start}{}",
+        in_path(path)
+    )]
+    MarkersOutOfOrder {
+        /// Name of the section.
+        section: String,
+        /// Location of the start marker.
+        start: Span,
+        /// Location of the end marker.
+        end: Span,
+        /// File that was searched, if any.
+        path: Option<PathBuf>,
+    },
+    /// A marker of a section appears more than once
+    #[error("Section `{section}` at {duplicate} is already defined at {first}{}", in_path(path))]
+    DuplicateSection {
+        /// Name of the section.
+        section: String,
+        /// Location of the first marker.
+        first: Span,
+        /// Location of the repeated marker.
+        duplicate: Span,
+        /// File that was searched, if any.
+        path: Option<PathBuf>,
+    },
+    /// Two requested sections overlap
+    #[error("Section `{section}` at {start} overlaps section `{other}`{}", in_path(path))]
+    OverlappingSections {
+        /// Name of the section that starts inside the other one.
+        section: String,
+        /// Name of the enclosing section.
+        other: String,
+        /// Location of the start marker of `section`.
+        start: Span,
+        /// File that was searched, if any.
+        path: Option<PathBuf>,
+    },
+    /// The same section is requested more than once in a single update
+    #[error("Section `{0}` is requested more than once")]
+    SectionRequestedTwice(String),
+    /// The new content of a section lacks the markers of an inner section it must preserve
+    #[error("Section `{section}` would drop its inner section `{inner}`, which is preserved")]
+    NestedSectionDropped {
+        /// Name of the outer section.
+        section: String,
+        /// Name of the inner section missing from the new content.
+        inner: String,
+    },
+    /// A section name can't be written in a marker
+    #[error("Invalid section name `{section}`: {reason}")]
+    InvalidSectionName {
+        /// The rejected name.
+        section: String,
+        /// Why the name was rejected.
+        reason: String,
+    },
+    /// The markers of a section can't be created where they were asked to be
+    #[error("Cannot create section `{section}`: {target} not found")]
+    InsertionPointNotFound {
+        /// Name of the section to create.
+        section: String,
+        /// What the section should have been created next to.
+        target: String,
+    },
+    /// A policy for creating missing sections is malformed
+    #[error("Invalid insertion policy: {0}")]
+    InvalidInsert(String),
+    /// A section was edited by hand since the content hash in its end marker was written
+    #[error(
+        "Section `{section}` was edited since it was generated, so it was left untouched{}",
+        in_path(path)
+    )]
+    SectionEdited {
+        /// Name of the section.
+        section: String,
+        /// Location of the end marker.
+        end: Span,
+        /// Hash stamped in the end marker, which the current content no longer has.
+        stamped: String,
+        /// File that was searched, if any.
+        path: Option<PathBuf>,
+    },
+    /// A policy for hand-edited sections is malformed
+    #[error("Invalid stamp policy: {0}")]
+    InvalidStamp(String),
+    /// A marker format is malformed
+    #[error("Invalid marker format: {0}")]
+    InvalidMarkerFormat(String),
+    /// A file changed while it was being updated
+    #[error(
+        "{} was modified by another process during the update, so it was left untouched",
+        path.display()
+    )]
+    ConcurrentModification {
+        /// The file being updated.
+        path: PathBuf,
+    },
+    /// A backup asked for doesn't exist
+    #[error("No backup `{id}` of {}", path.display())]
+    BackupNotFound {
+        /// Id of the backup.
+        id: String,
+        /// File the backup was looked for.
+        path: PathBuf,
+    },
+    /// A glob pattern selecting files is malformed
+    #[error("Invalid glob pattern `{pattern}`: {reason}")]
+    InvalidGlob {
+        /// The rejected pattern.
+        pattern: String,
+        /// Why the pattern was rejected.
+        reason: String,
+    },
+    /// The attributes of a section marker are malformed
+    #[error("Invalid marker attribute: {0}")]
+    InvalidAttribute(String),
+    /// The comment-block sections of a document are malformed
+    #[error(
+        "Invalid sections{}: {}",
+        in_path(path),
+        problems.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    )]
+    InvalidSections {
+        /// Structural problems, ordered by line.
+        problems: Vec<SectionProblem>,
+        /// File that was scanned, if any.
+        path: Option<PathBuf>,
+    },
+    /// A generated section differs from the content rendered for it
+    #[error("Section `{section}` is out of date")]
+    StaleSection {
+        /// Name of the stale section.
+        section: String,
+        /// Line-level diff from the current to the up-to-date document.
+        diff: Diff,
+    },
 }
 
 impl ParserError {
@@ -84,7 +270,53 @@ impl ParserError {
     pub fn is_channel_error(&self) -> bool {
         matches!(self, Self::ChannelError(..))
     }
+
+    /// Returns the error with `file` as the path of the document it was found in, unless it
+    /// already has one. Errors without a location are returned unchanged.
+    #[must_use]
+    pub fn with_path(mut self, file: &Path) -> Self {
+        if let Self::MarkerNotFound { path, .. }
+        | Self::MarkersOutOfOrder { path, .. }
+        | Self::DuplicateSection { path, .. }
+        | Self::OverlappingSections { path, .. }
+        | Self::SectionEdited { path, .. }
+        | Self::InvalidSections { path, .. } = &mut self
+        {
+            path.get_or_insert_with(|| file.to_path_buf());
+        }
+        self
+    }
+
+    /// Returns the message of the error without the ` in {path}` of located errors, for
+    /// renderers that show the path on its own.
+    pub fn message_without_path(&self) -> String {
+        let message = self.to_string();
+        match self {
+            Self::MarkerNotFound { path: Some(path), .. }
+            | Self::MarkersOutOfOrder { path: Some(path), .. }
+            | Self::DuplicateSection { path: Some(path), .. }
+            | Self::OverlappingSections { path: Some(path), .. }
+            | Self::SectionEdited { path: Some(path), .. }
+            | Self::InvalidSections { path: Some(path), .. } => {
+                message.replacen(&in_path(&Some(path.clone())), "", 1)
+            }
+            _ => message,
+        }
+    }
+
+    /// Returns `true` if the parser error is [`StaleSection`].
+    ///
+    /// [`StaleSection`]: ParserError::StaleSection
+    #[must_use]
+    pub fn is_stale_section(&self) -> bool {
+        matches!(self, Self::StaleSection { .. })
+    }
+}
+
+fn in_path(path: &Option<PathBuf>) -> String {
+    path.as_ref().map(|path| format!(" in {}", path.display())).unwrap_or_default()
 }
+
 // impl FromResidual<result::Result<Infallible, Box<dyn Any + Send>>> for ParserError {
 //     fn from_residual(residual: result::Result<Infallible, Box<dyn Any + Send>>) -> Self {
 //         todo!()
@@ -107,7 +339,7 @@ impl ParserError {
 #[derive(Debug, thiserror::Error)]
 pub enum PrinterError {
     /// An error occurred while using the termcolor library
-    #[error("Termcolor error")]
+    #[error("Termcolor error: {0}")]
     TermcolorError(#[from] termcolor::ColorChoiceParseError),
     /// An error occurred while performing an I/O operation
     #[error("File I/O error")]
@@ -132,6 +364,20 @@ pub enum ErrorColor {
     Cyan,
 }
 
+/// Converts an `ErrorColor` into the terminal color it is printed with.
+impl From<&ErrorColor> for termcolor::Color {
+    fn from(color: &ErrorColor) -> Self {
+        match color {
+            ErrorColor::Red => termcolor::Color::Red,
+            ErrorColor::Green => termcolor::Color::Green,
+            ErrorColor::Yellow => termcolor::Color::Yellow,
+            ErrorColor::Blue => termcolor::Color::Blue,
+            ErrorColor::Magenta => termcolor::Color::Magenta,
+            ErrorColor::Cyan => termcolor::Color::Cyan,
+        }
+    }
+}
+
 /// Converts a ParserError from a RegexError.
 impl From<ParserError> for RegexError {
     fn from(val: ParserError) -> Self {
@@ -198,6 +444,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn should_locate_marker_errors() {
+        let err = ParserError::MarkersOutOfOrder {
+            section: "tag_1".to_string(),
+            start: Span::new(4, "<!--START_SECTION:tag_1-->", 0..26),
+            end: Span::new(1, "  <!--END_SECTION:tag_1-->", 2..26),
+            path: None,
+        };
+        assert_eq!(
+            err.with_path(Path::new("README.md")).to_string(),
+            "End marker of section `tag_1` at 2:3 comes before its start marker at 5:1 in README.md"
+        );
+    }
+
     #[test]
     fn should_log_printererror() {
         fn parse_input() -> Result<()> {
@@ -0,0 +1,266 @@
+//! `format` is a Rust module for the wording of section markers, so files already managed by other
+//! README generators can be taken over without rewriting their markers.
+//!
+//! A [`MarkerFormat`] holds a template for the start and the end keyword, written inside the
+//! comment delimiters of the [`CommentStyle`], where `{name}` stands for the section name:
+//!
+//! | Format                           | Start marker                    | End marker                    |
+//! |----------------------------------|---------------------------------|-------------------------------|
+//! | [`MarkerFormat::section`]        | `<!--START_SECTION:x-->`        | `<!--END_SECTION:x-->`        |
+//! | [`MarkerFormat::begin_end`]      | `<!-- BEGIN x -->`              | `<!-- END x -->`              |
+//! | [`MarkerFormat::name_start_end`] | `<!-- x:start -->`              | `<!-- x:end -->`              |
+//! | [`MarkerFormat::blog_post_list`] | `<!-- BLOG-POST-LIST:START -->` | `<!-- BLOG-POST-LIST:END -->` |
+//!
+//! Attributes follow the start keyword, e.g. `<!-- x:start sort=stars -->`. Templates without
+//! `{name}` mark a single section per document, whatever the name of the block.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use parser::findrepl::{replace_in_str, CommentBlock, MarkerFormat};
+//!
+//! let buf = "<!-- BLOG-POST-LIST:START -->\nold\n<!-- BLOG-POST-LIST:END -->\n";
+//! let block = CommentBlock::new("posts".to_string()).with_format(MarkerFormat::blog_post_list());
+//!
+//! let replaced = replace_in_str("* new", &block, buf).unwrap();
+//! assert_eq!(replaced.content, "<!-- BLOG-POST-LIST:START -->\n* new\n<!-- BLOG-POST-LIST:END -->\n");
+//! ```
+
+use std::str::FromStr;
+
+use regex::Regex;
+
+use crate::{error::ParserError, findrepl::Marker, style::CommentStyle};
+
+/// The placeholder of the section name in the templates of a [`MarkerFormat`].
+const NAME: &str = "{name}";
+
+/// `MarkerFormat` is the wording of the start and end markers of a section, inside their comment
+/// delimiters. See the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkerFormat {
+    start: String,
+    end: String,
+}
+
+impl Default for MarkerFormat {
+    fn default() -> Self {
+        Self::section()
+    }
+}
+
+impl MarkerFormat {
+    /// Creates a [`MarkerFormat`] from the `start` and `end` keyword templates, where `{name}`
+    /// stands for the section name.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`ParserError::InvalidMarkerFormat`] if a template is empty,
+    /// spans more than one line or has `{name}` more than once, if only one of them has `{name}`,
+    /// or if they are the same.
+    pub fn new(start: &str, end: &str) -> super::Result<Self> {
+        let invalid = |reason: &str| {
+            ParserError::InvalidMarkerFormat(format!("{reason} in `{start}` and `{end}`"))
+        };
+        let (start, end) = (start.trim(), end.trim());
+        for template in [start, end] {
+            if template.is_empty() {
+                return Err(invalid("a template is empty"));
+            }
+            if template.contains(['\n', '\r']) {
+                return Err(invalid("a template spans more than one line"));
+            }
+            if template.matches(NAME).count() > 1 {
+                return Err(invalid("a template has `{name}` more than once"));
+            }
+        }
+        if start.contains(NAME) != end.contains(NAME) {
+            return Err(invalid("only one template has `{name}`"));
+        }
+        if start == end {
+            return Err(invalid("the templates are the same"));
+        }
+
+        Ok(Self { start: start.to_string(), end: end.to_string() })
+    }
+
+    /// `<!--START_SECTION:x-->` and `<!--END_SECTION:x-->`, the default.
+    pub fn section() -> Self {
+        Self { start: "START_SECTION:{name}".to_string(), end: "END_SECTION:{name}".to_string() }
+    }
+
+    /// `<!-- BEGIN x -->` and `<!-- END x -->`.
+    pub fn begin_end() -> Self {
+        Self { start: "BEGIN {name}".to_string(), end: "END {name}".to_string() }
+    }
+
+    /// `<!-- x:start -->` and `<!-- x:end -->`.
+    pub fn name_start_end() -> Self {
+        Self { start: "{name}:start".to_string(), end: "{name}:end".to_string() }
+    }
+
+    /// `<!-- BLOG-POST-LIST:START -->` and `<!-- BLOG-POST-LIST:END -->`, as written by
+    /// `blog-post-workflow`.
+    pub fn blog_post_list() -> Self {
+        Self { start: "BLOG-POST-LIST:START".to_string(), end: "BLOG-POST-LIST:END".to_string() }
+    }
+
+    /// Returns the template of the `marker` keyword.
+    pub fn template(&self, marker: &Marker) -> &str {
+        match marker {
+            Marker::Start => &self.start,
+            Marker::End => &self.end,
+        }
+    }
+
+    /// Returns `true` if the templates have no `{name}`, so the markers are the same for every
+    /// section.
+    pub fn is_nameless(&self) -> bool {
+        !self.start.contains(NAME)
+    }
+
+    /// Returns the `marker` keyword of the section `name`, e.g. `START_SECTION:x`.
+    pub(crate) fn keyword(&self, marker: &Marker, name: &str) -> String {
+        self.template(marker).replace(NAME, name)
+    }
+
+    /// Returns the pattern matching the `marker` keyword of the section `name`, which is matched
+    /// literally. Whitespace is optional around the name and may be repeated within the
+    /// template.
+    pub(crate) fn keyword_pattern(&self, marker: &Marker, name: &str) -> String {
+        match self.template(marker).split_once(NAME) {
+            Some((before, after)) => {
+                format!(r"{}{}{}", literal(before), regex::escape(name), literal(after))
+            }
+            None => literal(self.template(marker)),
+        }
+    }
+
+    /// Returns the name of the sections scanned with this format if it [is
+    /// nameless](Self::is_nameless): its start template.
+    pub(crate) fn nameless_name(&self) -> &str {
+        &self.start
+    }
+
+    /// Builds the regex matching any start or end marker of this format written in `style`. The
+    /// `start` or `end` group captures the section name, followed by its attributes if the name
+    /// ends the template, or else the `start_attrs` or `end_attrs` group captures the attributes.
+    /// Markers of line-comment styles run to the end of the line.
+    pub(crate) fn scan_regex(&self, style: &CommentStyle) -> Regex {
+        let keyword = |template: &str, group: &str| match template.split_once(NAME) {
+            Some((before, after)) if after.trim().is_empty() => {
+                format!(r"{}(?P<{group}>.+?)", literal(before))
+            }
+            Some((before, after)) => format!(
+                r"{}(?P<{group}>.+?){}(?P<{group}_attrs>(?:\s+.*?)?)",
+                literal(before),
+                literal(after)
+            ),
+            None => format!(r"(?P<{group}>){}(?P<{group}_attrs>(?:\s+.*?)?)", literal(template)),
+        };
+        let head = format!(
+            r"{}\s*(?:{}|{})",
+            regex::escape(style.prefix().trim()),
+            keyword(&self.start, "start"),
+            keyword(&self.end, "end")
+        );
+        let pattern = match style.suffix().trim() {
+            "" => format!(r"{head}\s*$"),
+            suffix => format!(r"{head}\s*{}", regex::escape(suffix)),
+        };
+        Regex::new(&pattern).expect("escaped marker pattern is a valid regex")
+    }
+}
+
+/// Parses `section`, `begin-end`, `name-start-end` or `blog-post-list`, or the start and end
+/// templates separated by `|`, e.g. `BEGIN {name}|END {name}`.
+impl FromStr for MarkerFormat {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "section" => Ok(Self::section()),
+            "begin-end" => Ok(Self::begin_end()),
+            "name-start-end" => Ok(Self::name_start_end()),
+            "blog-post-list" => Ok(Self::blog_post_list()),
+            s => match s.split_once('|') {
+                Some((start, end)) => Self::new(start, end),
+                None => Err(ParserError::InvalidMarkerFormat(format!(
+                    "expected `section`, `begin-end`, `name-start-end`, `blog-post-list` or \
+                     `<start>|<end>`, found `{s}`"
+                ))),
+            },
+        }
+    }
+}
+
+/// Returns the pattern matching the literal part `text` of a template. Runs of whitespace match
+/// any whitespace, and whitespace is optional where `text` meets the name unless it has some
+/// there.
+fn literal(text: &str) -> String {
+    if text.trim().is_empty() {
+        return match text.is_empty() {
+            true => String::new(),
+            false => r"\s+".to_string(),
+        };
+    }
+    let edge = |whitespace: bool| if whitespace { r"\s+" } else { r"\s*" };
+    let words: Vec<String> = text.split_whitespace().map(regex::escape).collect();
+
+    format!(
+        "{}{}{}",
+        edge(text.starts_with(char::is_whitespace)),
+        words.join(r"\s+"),
+        edge(text.ends_with(char::is_whitespace))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_match_markers_of_each_format() {
+        let cases = [
+            (MarkerFormat::section(), "<!--START_SECTION:x-->", "<!-- END_SECTION: x -->"),
+            (MarkerFormat::begin_end(), "<!-- BEGIN x -->", "<!--END   x-->"),
+            (MarkerFormat::name_start_end(), "<!-- x:start -->", "<!-- x:end -->"),
+            (
+                MarkerFormat::blog_post_list(),
+                "<!-- BLOG-POST-LIST:START -->",
+                "<!-- BLOG-POST-LIST:END -->",
+            ),
+        ];
+        for (format, start, end) in cases {
+            let style = CommentStyle::Html;
+            let re_start = Regex::new(&format.keyword_pattern(&Marker::Start, "x")).unwrap();
+            let re_end = Regex::new(&format.keyword_pattern(&Marker::End, "x")).unwrap();
+            assert!(re_start.is_match(start) && !re_start.is_match(end), "{format:?}");
+            assert!(re_end.is_match(end) && !re_end.is_match(start), "{format:?}");
+
+            let scan = format.scan_regex(&style);
+            let caps = scan.captures(start).unwrap();
+            assert!(caps.name("start").is_some() && caps.name("end").is_none(), "{format:?}");
+            assert!(scan.captures(end).unwrap().name("end").is_some(), "{format:?}");
+        }
+
+        let format = MarkerFormat::name_start_end();
+        assert_eq!(format.keyword(&Marker::End, "tag_1"), "tag_1:end");
+        let caps = format.scan_regex(&CommentStyle::Hash).captures("# a b:start c=d").unwrap();
+        assert_eq!((&caps["start"], caps["start_attrs"].trim()), ("a b", "c=d"));
+    }
+
+    #[test]
+    fn should_parse_marker_formats() {
+        assert_eq!("begin-end".parse::<MarkerFormat>().unwrap(), MarkerFormat::begin_end());
+        let custom = "<{name}>|</{name}>".parse::<MarkerFormat>().unwrap();
+        assert_eq!(custom.template(&Marker::End), "</{name}>");
+        assert!(!custom.is_nameless() && MarkerFormat::blog_post_list().is_nameless());
+
+        for invalid in ["begin", "A {name}|B", "{name}|{name}", " |END", "{name}{name}|b {name}"] {
+            assert!(invalid.parse::<MarkerFormat>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -9,45 +9,387 @@
 
 #![deny(missing_debug_implementations, missing_docs)]
 
+mod attrs;
+mod backup;
+mod batch;
+pub mod diff;
 mod error;
+mod format;
+mod lock;
 mod macros;
+mod merge;
+mod scan;
+mod style;
 
 pub use crate::{error::*, findrepl::*};
 
-#[allow(dead_code)]
-mod printer {
-    use std::io::Write;
+pub mod printer {
+    //! # printer
+    //!
+    //! `printer` renders parser output to the terminal with colors: the unified diff of a
+    //! [`ReplaceOutcome`](crate::findrepl::ReplaceOutcome), and [`Diagnostic`]s that show the
+    //! source lines an error is about with carets under the offending markers:
+    //!
+    //! ```text
+    //! error: Section `tag_1` at 4:1 is already defined at 1:1
+    //!  --> README.md:4:1
+    //!   |
+    //! 1 | <!--START_SECTION:tag_1-->
+    //!   | -------------------------- first defined here
+    //! 4 | <!--START_SECTION:tag_1-->
+    //!   | ^^^^^^^^^^^^^^^^^^^^^^^^^^ defined again here
+    //! ```
+    //!
+    //! Colors follow [`set_color_choice`], which defaults to `auto`: colors are used only when
+    //! the output stream is a terminal and `NO_COLOR` is not set.
+
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+        sync::RwLock,
+    };
 
     use atty::Stream;
+    use lazy_static::lazy_static;
     use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
 
-    use crate::error::ParserError;
+    use crate::{
+        diff::{Diff, DiffTag},
+        error::{ErrorColor, ParserError, PrinterError, Span},
+    };
+
+    lazy_static! {
+        /// Color choice set by the user, e.g. with `--color=always`.
+        static ref COLOR_CHOICE: RwLock<ColorChoice> = RwLock::new(ColorChoice::Auto);
+    }
+
+    /// Sets whether colors are used by every function of this module.
+    pub fn set_color_choice(choice: ColorChoice) {
+        *COLOR_CHOICE.write().unwrap_or_else(|e| e.into_inner()) = choice;
+    }
+
+    /// Parses a `--color` value: `auto`, `always` or `never`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`PrinterError::TermcolorError`] for any other value.
+    pub fn parse_color_choice(value: &str) -> Result<ColorChoice, ParserError> {
+        Ok(value.parse().map_err(PrinterError::from)?)
+    }
+
+    /// Returns the color choice for `stream`: the one set with [`set_color_choice`], where
+    /// `auto` turns into [`ColorChoice::Never`] if `stream` is not a terminal or `NO_COLOR` is set.
+    pub(crate) fn color_choice(stream: Stream) -> ColorChoice {
+        let choice = *COLOR_CHOICE.read().unwrap_or_else(|e| e.into_inner());
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        resolve_color_choice(choice, atty::is(stream), no_color)
+    }
 
-    pub(crate) fn new() -> Result<(), ParserError> {
-        let bufwtr = BufferWriter::stderr(ColorChoice::Always);
+    fn resolve_color_choice(choice: ColorChoice, is_tty: bool, no_color: bool) -> ColorChoice {
+        match choice {
+            ColorChoice::Auto if no_color || !is_tty => ColorChoice::Never,
+            choice => choice,
+        }
+    }
+
+    /// `print_diff` prints `diff` of the file at `path` to stdout as a colored unified diff.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing to stdout fails.
+    pub fn print_diff(diff: &Diff, path: &Path) -> Result<(), ParserError> {
+        let bufwtr = BufferWriter::stdout(color_choice(Stream::Stdout));
         let mut buffer = bufwtr.buffer();
-        buffer.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
-        writeln!(&mut buffer, "green text!")?;
+        write_diff(&mut buffer, diff, path)?;
         bufwtr.print(&buffer)?;
 
         Ok(())
     }
 
-    pub(crate) fn is_stdout_tty(stream: Stream) -> Result<(), ParserError> {
-        let bufwtr = BufferWriter::stderr(ColorChoice::Always);
+    /// `write_diff` writes `diff` of the file at `path` to `wtr` as a unified diff, with hunk
+    /// headers in cyan, removed lines in red and added lines in green.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing to `wtr` fails.
+    pub fn write_diff<W: WriteColor>(
+        wtr: &mut W,
+        diff: &Diff,
+        path: &Path,
+    ) -> Result<(), ParserError> {
+        let path = path.display();
+        wtr.set_color(ColorSpec::new().set_bold(true))?;
+        writeln!(wtr, "--- a/{path}")?;
+        writeln!(wtr, "+++ b/{path}")?;
+        for hunk in &diff.hunks {
+            wtr.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+            writeln!(wtr, "{}", hunk.header())?;
+            for line in &hunk.lines {
+                match line.tag {
+                    DiffTag::Equal => wtr.reset()?,
+                    DiffTag::Delete => wtr.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?,
+                    DiffTag::Insert => {
+                        wtr.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?
+                    }
+                }
+                writeln!(wtr, "{}{}", line.tag.sign(), line.content)?;
+            }
+        }
+        wtr.reset()?;
+
+        Ok(())
+    }
+
+    /// `Label` points at a span of the source with a short message.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Label {
+        /// Location the label points at. A span of length 0 covers the whole line.
+        pub span: Span,
+        /// Text printed after the carets.
+        pub message: String,
+        /// `true` for the location of the error, `false` for related locations.
+        pub primary: bool,
+    }
+
+    /// `Diagnostic` is an error message with the source locations it is about.
+    #[derive(Debug, Default, Clone, PartialEq, Eq)]
+    pub struct Diagnostic {
+        /// Headline of the diagnostic.
+        pub message: String,
+        /// File the labels point into, if any.
+        pub path: Option<PathBuf>,
+        /// Source locations, printed in line order.
+        pub labels: Vec<Label>,
+    }
+
+    impl Diagnostic {
+        /// Creates a [`Diagnostic`] with `message` and no source locations.
+        pub fn new(message: impl Into<String>) -> Self {
+            Self { message: message.into(), ..Default::default() }
+        }
+
+        /// Sets the file the labels point into, unless one is already set.
+        #[must_use]
+        pub fn with_path(mut self, path: &Path) -> Self {
+            self.path.get_or_insert_with(|| path.to_path_buf());
+            self
+        }
+
+        /// Adds a label pointing at `span`.
+        #[must_use]
+        pub fn with_label(mut self, span: Span, message: impl Into<String>, primary: bool) -> Self {
+            self.labels.push(Label { span, message: message.into(), primary });
+            self
+        }
+    }
+
+    impl From<&ParserError> for Diagnostic {
+        fn from(error: &ParserError) -> Self {
+            let (message, path) = match error {
+                ParserError::MarkerNotFound { path, .. }
+                | ParserError::MarkersOutOfOrder { path, .. }
+                | ParserError::DuplicateSection { path, .. }
+                | ParserError::OverlappingSections { path, .. }
+                | ParserError::SectionEdited { path, .. }
+                | ParserError::InvalidSections { path, .. } => {
+                    // The path is shown below the message, next to the line and column.
+                    (error.message_without_path(), path.clone())
+                }
+                error => (error.to_string(), None),
+            };
+            let diagnostic = Diagnostic { message, path, labels: Vec::new() };
+
+            match error {
+                ParserError::MarkersOutOfOrder { start, end, .. } => diagnostic
+                    .with_label(*end, "end marker", true)
+                    .with_label(*start, "start marker", false),
+                ParserError::DuplicateSection { first, duplicate, .. } => diagnostic
+                    .with_label(*duplicate, "defined again here", true)
+                    .with_label(*first, "first defined here", false),
+                ParserError::OverlappingSections { other, start, .. } => {
+                    diagnostic.with_label(*start, format!("starts inside section `{other}`"), true)
+                }
+                ParserError::SectionEdited { end, stamped, .. } => diagnostic.with_label(
+                    *end,
+                    format!("content no longer matches hash {stamped}"),
+                    true,
+                ),
+                ParserError::InvalidSections { problems, .. } => {
+                    // Each problem is shown as a label, so the headline only counts them.
+                    let message = format!("Invalid sections: {} problem(s)", problems.len());
+                    problems.iter().fold(
+                        Diagnostic { message, ..diagnostic },
+                        |diagnostic, problem| {
+                            let span = Span { line: problem.line(), column: 0, len: 0 };
+                            diagnostic.with_label(span, problem.message(), true)
+                        },
+                    )
+                }
+                _ => diagnostic,
+            }
+        }
+    }
+
+    /// `print_error` prints `error` to stderr as a [`Diagnostic`], with the source lines it is
+    /// about if the file it was found in can be read.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing to stderr fails.
+    pub fn print_error(error: &ParserError) -> Result<(), ParserError> {
+        print_diagnostic(&Diagnostic::from(error))
+    }
+
+    /// `print_diagnostic` prints `diagnostic` to stderr, reading the source lines from its path.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing to stderr fails.
+    pub fn print_diagnostic(diagnostic: &Diagnostic) -> Result<(), ParserError> {
+        let source = diagnostic.path.as_ref().and_then(|path| fs::read_to_string(path).ok());
+        let bufwtr = BufferWriter::stderr(color_choice(Stream::Stderr));
         let mut buffer = bufwtr.buffer();
-        if atty::is(stream) {
-            buffer.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
-            writeln!(&mut buffer, "I'm a terminal")?;
-            bufwtr.print(&buffer)?;
-        } else {
-            buffer.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
-            writeln!(&mut buffer, "I'm not a terminal")?;
-            bufwtr.print(&buffer)?;
+        write_diagnostic(&mut buffer, diagnostic, source.as_deref())?;
+        bufwtr.print(&buffer)?;
+
+        Ok(())
+    }
+
+    /// `write_diagnostic` writes `diagnostic` to `wtr`. Each label is shown under its line of
+    /// `source`, with `^` carets for primary labels and `-` for the others. Labels whose line is
+    /// not in `source` are written as notes.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing to `wtr` fails.
+    pub fn write_diagnostic<W: WriteColor>(
+        wtr: &mut W,
+        diagnostic: &Diagnostic,
+        source: Option<&str>,
+    ) -> Result<(), ParserError> {
+        let red =
+            ColorSpec::new().set_fg(Some(Color::from(&ErrorColor::Red))).set_bold(true).clone();
+        let blue =
+            ColorSpec::new().set_fg(Some(Color::from(&ErrorColor::Blue))).set_bold(true).clone();
+
+        wtr.set_color(&red)?;
+        write!(wtr, "error")?;
+        wtr.set_color(ColorSpec::new().set_bold(true))?;
+        writeln!(wtr, ": {}", diagnostic.message)?;
+        wtr.reset()?;
+
+        let mut labels: Vec<&Label> = diagnostic.labels.iter().collect();
+        labels.sort_by_key(|label| (label.span.line, !label.primary));
+        let lines: Vec<&str> = source.map(|s| s.lines().collect()).unwrap_or_default();
+        let width = labels.iter().map(|l| (l.span.line + 1).to_string().len()).max().unwrap_or(1);
+        let gutter = " ".repeat(width);
+
+        let primary = labels.iter().find(|l| l.primary).or(labels.first());
+        if let Some(path) = &diagnostic.path {
+            wtr.set_color(&blue)?;
+            write!(wtr, "{gutter}--> ")?;
+            wtr.reset()?;
+            match primary {
+                Some(label) => writeln!(wtr, "{}:{}", path.display(), label.span)?,
+                None => writeln!(wtr, "{}", path.display())?,
+            }
+        }
+        if labels.iter().any(|label| label.span.line < lines.len()) {
+            wtr.set_color(&blue)?;
+            writeln!(wtr, "{gutter} |")?;
+        }
+        let mut last_line = None;
+        for label in labels {
+            let Some(line) = lines.get(label.span.line) else {
+                wtr.set_color(&blue)?;
+                write!(wtr, "{gutter} = ")?;
+                wtr.reset()?;
+                writeln!(wtr, "{}: {}", label.span, label.message)?;
+                continue;
+            };
+            let line = line.trim_start_matches('\u{feff}').replace('\t', "    ");
+            if last_line != Some(label.span.line) {
+                wtr.set_color(&blue)?;
+                write!(wtr, "{:>width$} | ", label.span.line + 1)?;
+                wtr.reset()?;
+                writeln!(wtr, "{line}")?;
+                last_line = Some(label.span.line);
+            }
+
+            let (column, len) = match label.span.len {
+                0 => {
+                    let indent = line.len() - line.trim_start().len();
+                    (indent, line.trim().chars().count())
+                }
+                len => (label.span.column, len),
+            };
+            wtr.set_color(&blue)?;
+            write!(wtr, "{gutter} | ")?;
+            wtr.set_color(if label.primary { &red } else { &blue })?;
+            let marker = if label.primary { "^" } else { "-" };
+            writeln!(wtr, "{}{} {}", " ".repeat(column), marker.repeat(len.max(1)), label.message)?;
         }
+        wtr.reset()?;
 
         Ok(())
     }
+
+    #[cfg(test)]
+    mod tests {
+        use pretty_assertions::assert_eq;
+        use termcolor::NoColor;
+
+        use super::*;
+        use crate::findrepl::{replace_in_str, scan_sections, CommentBlock};
+
+        #[test]
+        fn should_write_unified_diff() {
+            let diff = Diff::lines("a\nb\n", "a\nc\n");
+            let mut wtr = NoColor::new(Vec::new());
+            write_diff(&mut wtr, &diff, Path::new("README.md")).unwrap();
+            assert_eq!(
+                String::from_utf8(wtr.into_inner()).unwrap(),
+                "--- a/README.md\n+++ b/README.md\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n"
+            );
+        }
+
+        #[test]
+        fn should_write_diagnostic_with_carets() {
+            let buf = "<!--START_SECTION:tag_1-->\n<!--END_SECTION:tag_1-->\n\n\
+                       \x20 <!--START_SECTION:tag_1-->\n<!--END_SECTION:tag_1-->\n";
+            let error = replace_in_str("", &CommentBlock::new("tag_1".to_string()), buf)
+                .unwrap_err()
+                .with_path(Path::new("README.md"));
+            let mut wtr = NoColor::new(Vec::new());
+            write_diagnostic(&mut wtr, &Diagnostic::from(&error), Some(buf)).unwrap();
+            assert_eq!(
+                String::from_utf8(wtr.into_inner()).unwrap(),
+                "error: Section `tag_1` at 4:3 is already defined at 1:1\n \
+                 --> README.md:4:3\n  |\n\
+                 1 | <!--START_SECTION:tag_1-->\n  | -------------------------- first defined here\n\
+                 4 |   <!--START_SECTION:tag_1-->\n  |   ^^^^^^^^^^^^^^^^^^^^^^^^^^ defined again here\n"
+            );
+
+            let error = scan_sections("a\n<!--END_SECTION:x-->\n").validate().unwrap_err();
+            let mut wtr = NoColor::new(Vec::new());
+            write_diagnostic(&mut wtr, &Diagnostic::from(&error), None).unwrap();
+            assert_eq!(
+                String::from_utf8(wtr.into_inner()).unwrap(),
+                "error: Invalid sections: 1 problem(s)\n  \
+                 = 2:1: end of section `x` has no start marker\n"
+            );
+        }
+
+        #[test]
+        fn should_resolve_color_choice() {
+            assert_eq!(parse_color_choice("never").unwrap(), ColorChoice::Never);
+            assert!(parse_color_choice("sometimes").is_err());
+            assert_eq!(resolve_color_choice(ColorChoice::Auto, true, false), ColorChoice::Auto);
+            assert_eq!(resolve_color_choice(ColorChoice::Auto, false, false), ColorChoice::Never);
+            assert_eq!(resolve_color_choice(ColorChoice::Auto, true, true), ColorChoice::Never);
+            assert_eq!(resolve_color_choice(ColorChoice::Always, false, true), ColorChoice::Always);
+        }
+    }
 }
 
 pub mod findrepl {
@@ -100,18 +442,39 @@ pub mod findrepl {
 
     use std::{
         self,
+        borrow::Cow,
         fmt::Display,
         fs,
-        io::{Read, Write},
+        io::{BufRead, BufReader, BufWriter, Read, Write},
         path::Path,
+        str::FromStr,
         sync::Arc,
     };
 
-    use anyhow::anyhow;
-    use rayon::prelude::*;
-    use regex::{Error::Syntax, Regex};
+    use regex::Regex;
 
-    use crate::{comment_block, error::ParserError};
+    use crate::{
+        attrs::split_name,
+        diff::Diff,
+        error::{ParserError, Span},
+        lock::{FileLock, Snapshot},
+        merge::merge_pinned,
+        scan::{code_block_lines, CodeBlocks},
+    };
+    pub use crate::{
+        attrs::{fmt_attributes, parse_attributes, Attributes},
+        backup::{Backup, Backups},
+        batch::{expand_globs, is_glob, replace_in_files, BatchFile, FileReport, FileStatus},
+        format::MarkerFormat,
+        scan::{
+            scan_sections, scan_sections_with, scan_sections_with_format, Section, SectionProblem,
+            SectionScan, SectionTree,
+        },
+        style::CommentStyle,
+    };
+
+    /// Attribute of the end marker holding the content hash of a section, see [`Stamp`].
+    const HASH: &str = "hash";
 
     /// `Marker` is an enumeration of marker values, `Start` and `End`.
     /// These markers are used to indicate the start and end of a [`CommentBlock`] comment block in
@@ -134,157 +497,1363 @@ pub mod findrepl {
         }
     }
 
-    /// `CommentBlock` is a struct that holds information about a comment block.
-    /// It has two fields: `section_name`, which is a `String` representing the name of the section,
-    /// and `marker`, which is a tuple of two Marker values, indicating the start and end
-    /// markers of the comment block.
+    /// `Nested` tells what happens to the sections nested inside a section when it is replaced.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub enum Nested {
+        /// The new text replaces everything between the markers, inner sections included.
+        #[default]
+        Regenerate,
+        /// Inner sections keep their current content. The new text must contain their markers,
+        /// and the lines between them are carried over from the document.
+        Preserve,
+    }
+
+    /// `Merge` tells what happens to the current lines of a section when it is replaced.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub enum Merge {
+        /// The new text replaces every line.
+        #[default]
+        Replace,
+        /// Lines pinned with a keep comment, e.g. `<!-- keep -->`, survive the replacement, next
+        /// to the generated line they followed or in place of the one linking to the same URL.
+        KeepPinned,
+    }
+
+    /// `Stamp` tells whether the end marker of a section records a hash of the content written
+    /// in it, e.g. `<!--END_SECTION:tag_1 hash=1a2b3c4d-->`, and what happens when the content no
+    /// longer matches that hash because it was edited by hand.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub enum Stamp {
+        /// No hash is written or checked.
+        #[default]
+        Off,
+        /// The hash is written, and a warning is logged before an edited section is overwritten.
+        Warn,
+        /// The hash is written, and an edited section is left untouched with
+        /// [`ParserError::SectionEdited`]. Removing the hash lets the next run overwrite it.
+        Refuse,
+    }
+
+    /// Parses `off`, `warn` or `refuse`.
+    impl FromStr for Stamp {
+        type Err = ParserError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "off" => Ok(Self::Off),
+                "warn" => Ok(Self::Warn),
+                "refuse" => Ok(Self::Refuse),
+                _ => Err(ParserError::InvalidStamp(format!(
+                    "expected `off`, `warn` or `refuse`, found `{s}`"
+                ))),
+            }
+        }
+    }
+
+    /// `Insert` tells where the markers of a section are created when a document has none of them
+    /// yet. Creating sections is opt-in: by default missing markers are an error.
+    #[derive(Debug, Default, Clone, PartialEq, Eq)]
+    pub enum Insert {
+        /// Fail with [`ParserError::MarkerNotFound`].
+        #[default]
+        Never,
+        /// Append the section at the end of the document.
+        Append,
+        /// Insert the section right after a heading, given with its `#`s to match its level too,
+        /// e.g. `## Projects`, or without them, e.g. `Projects`.
+        AfterHeading(String),
+        /// Insert the section right before the start marker of the section with this name.
+        BeforeSection(String),
+    }
+
+    /// Parses `never`, `append`, `after:<heading>` or `before:<section>`.
+    impl FromStr for Insert {
+        type Err = ParserError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.split_once(':') {
+                _ if s == "never" => Ok(Self::Never),
+                _ if s == "append" => Ok(Self::Append),
+                Some(("after", heading)) if !heading.trim().is_empty() => {
+                    Ok(Self::AfterHeading(heading.trim().to_string()))
+                }
+                Some(("before", section)) if !section.trim().is_empty() => {
+                    Ok(Self::BeforeSection(section.trim().to_string()))
+                }
+                _ => Err(ParserError::InvalidInsert(format!(
+                    "expected `never`, `append`, `after:<heading>` or `before:<section>`, found `{s}`"
+                ))),
+            }
+        }
+    }
+
+    /// `CommentBlock` is a section of a document between a start and an end marker, found by its
+    /// name.
+    ///
+    /// [`CommentBlock::new`] creates a block with the `<!--START_SECTION:name-->` markers of
+    /// Markdown. The `with_*` methods change how it is found and replaced: [`with_style`] and
+    /// [`with_format`] set the comment delimiters and wording of the markers, [`with_attribute`]
+    /// the attributes written after the name, [`with_indent`] whether the content is indented
+    /// like the start marker, [`with_nested`] what happens to the sections nested inside it,
+    /// [`with_merge`] what happens to its hand-pinned lines, [`with_stamp`] whether hand edits
+    /// are detected with a content hash, and [`with_insert`] where its markers are created if
+    /// missing.
+    ///
+    /// [`with_style`]: CommentBlock::with_style
+    /// [`with_format`]: CommentBlock::with_format
+    /// [`with_attribute`]: CommentBlock::with_attribute
+    /// [`with_indent`]: CommentBlock::with_indent
+    /// [`with_nested`]: CommentBlock::with_nested
+    /// [`with_merge`]: CommentBlock::with_merge
+    /// [`with_stamp`]: CommentBlock::with_stamp
+    /// [`with_insert`]: CommentBlock::with_insert
     #[derive(Debug, Default, Clone, PartialEq)]
     pub struct CommentBlock {
         section_name: String,
         marker: (Marker, Marker),
+        style: Option<CommentStyle>,
+        format: MarkerFormat,
+        attributes: Attributes,
+        indent: bool,
+        nested: Nested,
+        merge: Merge,
+        stamp: Stamp,
+        insert: Insert,
+    }
+
+    /// `impl CommentBlock` is an implementation block for the CommentBlock struct.
+    ///
+    /// It provides a constructor new for creating new CommentBlock values, and two methods:
+    /// `start_marker` and `end_marker`, which return the start and end markers as `String` values.
+    impl CommentBlock {
+        /// Creates a new [`CommentBlock`].
+        ///
+        /// The comment style is picked from the extension of the file the block is used with,
+        /// unless one is set with [`CommentBlock::with_style`].
+        pub fn new(section_name: String) -> Self {
+            Self {
+                section_name: section_name.trim().to_string(),
+                marker: (Marker::Start, Marker::End),
+                style: None,
+                format: MarkerFormat::section(),
+                attributes: Attributes::new(),
+                indent: false,
+                nested: Nested::Regenerate,
+                merge: Merge::Replace,
+                stamp: Stamp::Off,
+                insert: Insert::Never,
+            }
+        }
+
+        /// Sets an attribute written after the section name in the start marker, e.g.
+        /// `<!--START_SECTION:repos sort=stars-->`.
+        ///
+        /// Attributes only affect markers this block writes. Existing markers are matched with
+        /// whatever attributes they carry, and those are kept verbatim.
+        #[must_use]
+        pub fn with_attribute(mut self, key: &str, value: &str) -> Self {
+            self.attributes.insert(key.to_string(), value.to_string());
+            self
+        }
+
+        /// Returns the attributes written in the start marker by this block.
+        pub fn attributes(&self) -> &Attributes {
+            &self.attributes
+        }
+
+        /// Sets the comment style of the markers explicitly.
+        #[must_use]
+        pub fn with_style(mut self, style: CommentStyle) -> Self {
+            self.style = Some(style);
+            self
+        }
+
+        /// Sets the wording of the markers, e.g. [`MarkerFormat::begin_end`] for
+        /// `<!-- BEGIN tag_1 -->` and `<!-- END tag_1 -->`. See [`MarkerFormat`].
+        #[must_use]
+        pub fn with_format(mut self, format: MarkerFormat) -> Self {
+            self.format = format;
+            self
+        }
+
+        /// Returns the wording of the markers.
+        pub fn format(&self) -> &MarkerFormat {
+            &self.format
+        }
+
+        /// Indents every non-empty line of the content with the leading whitespace of the start
+        /// marker, so sections nested in list items or `<details>` blocks render as part of them.
+        #[must_use]
+        pub fn with_indent(mut self, indent: bool) -> Self {
+            self.indent = indent;
+            self
+        }
+
+        /// Sets whether the sections nested inside this one are regenerated or preserved when it
+        /// is replaced. See [`Nested`].
+        #[must_use]
+        pub fn with_nested(mut self, nested: Nested) -> Self {
+            self.nested = nested;
+            self
+        }
+
+        /// Sets whether the lines pinned in the current section survive when it is replaced. See
+        /// [`Merge`].
+        #[must_use]
+        pub fn with_merge(mut self, merge: Merge) -> Self {
+            self.merge = merge;
+            self
+        }
+
+        /// Sets whether the end marker records a hash of the content, to detect hand edits on the
+        /// next replacement. See [`Stamp`].
+        #[must_use]
+        pub fn with_stamp(mut self, stamp: Stamp) -> Self {
+            self.stamp = stamp;
+            self
+        }
+
+        /// Sets where the markers of the section are created if a document has none of them, with
+        /// the attributes of this block. See [`Insert`].
+        #[must_use]
+        pub fn with_insert(mut self, insert: Insert) -> Self {
+            self.insert = insert;
+            self
+        }
+
+        /// Returns the name of the section.
+        pub fn section_name(&self) -> &str {
+            &self.section_name
+        }
+
+        /// Returns the comment style of the markers, [`CommentStyle::Html`] if none was set.
+        pub fn style(&self) -> CommentStyle {
+            self.style.clone().unwrap_or_default()
+        }
+
+        /// Returns the block with its comment style picked from `path`, unless one was set
+        /// explicitly.
+        #[must_use]
+        pub fn for_path(mut self, path: &Path) -> Self {
+            self.style.get_or_insert_with(|| CommentStyle::from_path(path));
+            self
+        }
+
+        /// Returns the start marker, e.g. `<!--START_SECTION:tag_1-->`, with the attributes of
+        /// the block after the section name.
+        pub fn start_marker(&self) -> String {
+            let head = self.format.keyword(&self.marker.0, &self.section_name);
+            match self.attributes.is_empty() {
+                true => self.style().wrap(&head),
+                false => self.style().wrap(&format!("{head} {}", fmt_attributes(&self.attributes))),
+            }
+        }
+
+        /// Returns the end marker, e.g. `<!--END_SECTION:tag_1-->`.
+        pub fn end_marker(&self) -> String {
+            self.style().wrap(&self.format.keyword(&self.marker.1, &self.section_name))
+        }
+
+        /// Checks that the section name can be written in a marker and read back.
+        ///
+        /// # Errors
+        ///
+        /// This function will return [`ParserError::InvalidSectionName`] if the name is empty,
+        /// spans more than one line, contains a comment delimiter of the style, or has a word
+        /// that reads as a `key=value` attribute.
+        pub fn validate_name(&self) -> super::Result<()> {
+            let invalid = |reason: &str| ParserError::InvalidSectionName {
+                section: self.section_name.clone(),
+                reason: reason.to_string(),
+            };
+            let style = self.style();
+            if self.section_name.is_empty() {
+                return Err(invalid("the name is empty"));
+            }
+            if self.section_name.contains(['\n', '\r']) {
+                return Err(invalid("the name spans more than one line"));
+            }
+            let delimiters = [style.prefix().trim(), style.suffix().trim()];
+            if let Some(delimiter) =
+                delimiters.into_iter().find(|d| !d.is_empty() && self.section_name.contains(d))
+            {
+                return Err(invalid(&format!(
+                    "the name contains the comment delimiter `{delimiter}`"
+                )));
+            }
+            if split_name(&self.section_name).0 != self.section_name {
+                return Err(invalid("the name contains a `key=value` attribute"));
+            }
+            Ok(())
+        }
+
+        /// Returns the regex matching the `marker` of this block, capturing any attributes after
+        /// the section name in the `attrs` group.
+        ///
+        /// The section name is matched literally, and whitespace is optional inside the comment
+        /// delimiters, so hand-written markers such as `<!-- START_SECTION: tag_1 -->` are found.
+        pub(crate) fn marker_pattern(&self, marker: &Marker) -> String {
+            let style = self.style();
+            let head = format!(
+                r"{}\s*{}",
+                regex::escape(style.prefix().trim()),
+                self.format.keyword_pattern(marker, &self.section_name)
+            );
+            match style.suffix().trim() {
+                "" => format!(r"(?m){head}(?:\s+(?P<attrs>.*?))?\s*$"),
+                suffix => format!(r"{head}(?:\s+(?P<attrs>.*?))?\s*{}", regex::escape(suffix)),
+            }
+        }
+    }
+
+    /// `Replaced` is the outcome of splicing new content into the sections of a document held in
+    /// memory.
+    #[derive(Debug, Default, Clone, PartialEq, Eq)]
+    pub struct Replaced {
+        /// The full document with the updated sections.
+        pub content: String,
+        /// `true` if `content` differs from the original document.
+        pub changed: bool,
+        /// `true` if markers were missing and created as asked by the [`Insert`] policy of their
+        /// block.
+        pub created: bool,
+    }
+
+    /// `ReplaceOutcome` describes what a replacement would do to a document.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum ReplaceOutcome {
+        /// The sections already hold the requested content.
+        Unchanged,
+        /// The document would change as described by `diff`.
+        Changed {
+            /// Line-level diff from the current to the updated document.
+            diff: Diff,
+        },
+    }
+
+    impl ReplaceOutcome {
+        /// Creates a [`ReplaceOutcome`] by comparing the `original` document with `replaced`.
+        pub fn new(original: &str, replaced: &Replaced) -> Self {
+            match replaced.changed {
+                true => Self::Changed { diff: Diff::lines(original, &replaced.content) },
+                false => Self::Unchanged,
+            }
+        }
+
+        /// Returns `true` if the outcome is [`ReplaceOutcome::Changed`].
+        #[must_use]
+        pub fn is_changed(&self) -> bool {
+            matches!(self, Self::Changed { .. })
+        }
+    }
+
+    /// `replace_in_str` replaces the content between the start and end markers of `block` in
+    /// `buf` with `text`, without touching the file system.
+    ///
+    /// ```rust
+    /// use parser::*;
+    ///
+    /// let buf = "# Title\n<!--START_SECTION:tag_1-->\nold\n<!--END_SECTION:tag_1-->\n";
+    /// let block = CommentBlock::new("tag_1".to_string());
+    ///
+    /// let replaced = replace_in_str("new", &block, buf).unwrap();
+    /// assert!(replaced.changed);
+    /// assert_eq!(
+    ///     replaced.content,
+    ///     "# Title\n<!--START_SECTION:tag_1-->\nnew\n<!--END_SECTION:tag_1-->\n"
+    /// );
+    /// ```
+    ///
+    /// If `buf` has no markers of the section, they are created first as asked by the [`Insert`]
+    /// policy of `block`, and the result is flagged as [`Replaced::created`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the start or end marker is not found, or if the
+    /// place the markers should be created at is not found.
+    pub fn replace_in_str(text: &str, block: &CommentBlock, buf: &str) -> super::Result<Replaced> {
+        let inserted = with_markers(buf, block)?;
+        let doc = inserted.as_deref().unwrap_or(buf);
+        let (start, end) = get_many_block_positions(doc, &[block])?[0];
+        let (n_start, n_end) = (start.line, end.line);
+        let text = with_merge(doc, (n_start, n_end), block, text);
+        let text = with_nested(doc, (n_start, n_end), block, &text)?;
+        let doc = with_stamp(doc, (start, end), block, &text)?;
+
+        let mut replaced = splice(&doc, &[(n_start, n_end, &text, block.indent)]);
+        replaced.changed = replaced.content != buf;
+        replaced.created = inserted.is_some();
+        Ok(replaced)
+    }
+
+    /// `check_in_str` verifies that the section of `block` in `buf` already holds `text`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`ParserError::StaleSection`], with the diff that would bring
+    /// `buf` up to date, if the content between the markers differs from `text`. It will also
+    /// return an error if the start or end marker is not found.
+    pub fn check_in_str(text: &str, block: &CommentBlock, buf: &str) -> super::Result<()> {
+        let replaced = replace_in_str(text, block, buf)?;
+        if !replaced.changed {
+            return Ok(());
+        }
+        Err(ParserError::StaleSection {
+            section: block.section_name.clone(),
+            diff: Diff::lines(buf, &replaced.content),
+        })
+    }
+
+    /// `replace_many_in_str` is the in-memory counterpart of [`replace_many`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`ParserError::SectionRequestedTwice`] if a section is requested
+    /// more than once, or an error if a marker is not found, or sections are out of order or
+    /// overlap.
+    pub fn replace_many_in_str(
+        sections: &[(CommentBlock, String)],
+        buf: &str,
+    ) -> super::Result<Replaced> {
+        let original = buf;
+        let mut doc = Cow::Borrowed(buf);
+        for (block, _) in sections {
+            if let Some(inserted) = with_markers(&doc, block)? {
+                doc = Cow::Owned(inserted);
+            }
+        }
+        let buf = doc.as_ref();
+
+        let blocks: Vec<&CommentBlock> = sections.iter().map(|(block, _)| block).collect();
+        let positions = get_many_block_positions(buf, &blocks)?;
+
+        // Splice the sections in the order they appear in the document.
+        let mut order: Vec<usize> = (0..sections.len()).collect();
+        order.sort_by_key(|&i| positions[i].0.line);
+        if let Some(w) = order.windows(2).find(|w| positions[w[1]].0.line < positions[w[0]].1.line)
+        {
+            return Err(ParserError::OverlappingSections {
+                section: sections[w[1]].0.section_name.clone(),
+                other: sections[w[0]].0.section_name.clone(),
+                start: positions[w[1]].0,
+                path: None,
+            });
+        }
+        let texts = order
+            .iter()
+            .map(|&i| {
+                let (start, end) = positions[i];
+                let text = with_merge(buf, (start.line, end.line), &sections[i].0, &sections[i].1);
+                with_nested(buf, (start.line, end.line), &sections[i].0, &text).map(Cow::into_owned)
+            })
+            .collect::<super::Result<Vec<_>>>()?;
+        let mut stamped = Cow::Borrowed(buf);
+        for (&i, text) in order.iter().zip(&texts) {
+            if let Cow::Owned(doc) = with_stamp(&stamped, positions[i], &sections[i].0, text)? {
+                stamped = Cow::Owned(doc);
+            }
+        }
+        let edits: Vec<(usize, usize, &str, bool)> = order
+            .into_iter()
+            .zip(&texts)
+            .map(|(i, text)| {
+                let (start, end) = positions[i];
+                (start.line, end.line, text.as_str(), sections[i].0.indent)
+            })
+            .collect();
+
+        let mut replaced = splice(&stamped, &edits);
+        replaced.changed = replaced.content != original;
+        replaced.created = matches!(doc, Cow::Owned(_));
+        Ok(replaced)
+    }
+
+    /// `replace_in_reader` reads a whole document from `reader`, replaces the section of `block`
+    /// with `text`, and writes the result to `writer`.
+    ///
+    /// Returns `true` if the written document differs from the one read.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if reading or writing fails, or if the start or end
+    /// marker is not found.
+    pub fn replace_in_reader<R: Read, W: Write>(
+        text: &str,
+        block: &CommentBlock,
+        mut reader: R,
+        mut writer: W,
+    ) -> super::Result<bool> {
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf)?;
+        let replaced = replace_in_str(text, block, &buf)?;
+        writer.write_all(replaced.content.as_bytes())?;
+        writer.flush()?;
+
+        Ok(replaced.changed)
+    }
+
+    /// `replace_many_in_reader` is the reader and writer counterpart of [`replace_many`].
+    ///
+    /// Returns `true` if the written document differs from the one read.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if reading or writing fails, or for any of the reasons
+    /// listed in [`replace_many_in_str`].
+    pub fn replace_many_in_reader<R: Read, W: Write>(
+        sections: &[(CommentBlock, String)],
+        mut reader: R,
+        mut writer: W,
+    ) -> super::Result<bool> {
+        let mut buf = String::new();
+        reader.read_to_string(&mut buf)?;
+        let replaced = replace_many_in_str(sections, &buf)?;
+        writer.write_all(replaced.content.as_bytes())?;
+        writer.flush()?;
+
+        Ok(replaced.changed)
+    }
+
+    /// `replace_streaming` copies the document read from `reader` to `writer` line by line,
+    /// replacing the section of `block` with `text` on the way.
+    ///
+    /// Unlike [`replace_in_reader`], the document is never held in memory: lines before the start
+    /// marker are copied as is, the lines of `text` are written after it, the old content is
+    /// skipped up to the end marker, and the rest is copied again. The output is the same as the
+    /// one of [`replace_in_str`]. Blocks that preserve their inner sections or pinned lines, or
+    /// stamp their content hash, need the whole section at hand, and blocks that may create their
+    /// markers the whole document, so they are replaced in memory.
+    ///
+    /// Returns `true` if the written document differs from the one read. Nothing sensible is
+    /// written on error, so `writer` should be discarded.
+    ///
+    /// ```rust
+    /// use parser::*;
+    ///
+    /// let buf = "<!--START_SECTION:tag_1-->\nold\n<!--END_SECTION:tag_1-->\n";
+    /// let block = CommentBlock::new("tag_1".to_string());
+    ///
+    /// let mut out = Vec::new();
+    /// assert!(replace_streaming("new", &block, buf.as_bytes(), &mut out).unwrap());
+    /// assert_eq!(out, b"<!--START_SECTION:tag_1-->\nnew\n<!--END_SECTION:tag_1-->\n");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if reading or writing fails, or for the same reasons
+    /// as [`replace_in_str`].
+    pub fn replace_streaming<R: BufRead, W: Write>(
+        text: &str,
+        block: &CommentBlock,
+        mut reader: R,
+        mut writer: W,
+    ) -> super::Result<bool> {
+        if block.nested == Nested::Preserve
+            || block.merge == Merge::KeepPinned
+            || block.stamp != Stamp::Off
+            || block.insert != Insert::Never
+        {
+            return replace_in_reader(text, block, reader, writer);
+        }
+        block.validate_name()?;
+        let re_start = Regex::new(&block.marker_pattern(&block.marker.0))?;
+        let re_end = Regex::new(&block.marker_pattern(&block.marker.1))?;
+
+        let mut code = CodeBlocks::new(&block.style());
+        let (mut start, mut end, mut duplicate_start, mut duplicate_end) = (None, None, None, None);
+        // The new content of the section, and how much of it the old content matched so far.
+        let (mut section, mut matched, mut changed) = (String::new(), 0, false);
+        let mut inside = false;
+        let mut line = String::new();
+        for n in 0.. {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            let content = line
+                .strip_suffix('\n')
+                .map_or(line.as_str(), |l| l.strip_suffix('\r').unwrap_or(l));
+            let is_code = code.is_code(match n {
+                0 => content.trim_start_matches('\u{feff}'),
+                _ => content,
+            });
+            let find = |re: &Regex| match is_code {
+                true => None,
+                false => re.find(content).map(|m| Span::new(n, content, m.range())),
+            };
+            let (found_start, found_end) = (find(&re_start), find(&re_end));
+
+            if let Some(span) = found_end {
+                match end {
+                    None => {
+                        end = Some(span);
+                        inside = false;
+                    }
+                    Some(_) => duplicate_end = duplicate_end.or(Some(span)),
+                }
+            }
+            if let (true, Some(span)) = (start.is_some(), found_start) {
+                duplicate_start = duplicate_start.or(Some(span));
+            }
+            if inside {
+                // Skip the old content, only noting whether it differs from the new one.
+                match section[matched..].starts_with(line.as_str()) {
+                    true => matched += line.len(),
+                    false => changed = true,
+                }
+                continue;
+            }
+            writer.write_all(line.as_bytes())?;
+            if let Some(span) = found_start {
+                if start.is_some() {
+                    continue;
+                }
+                start = Some(span);
+                inside = end.is_none();
+                let newline = if line.ends_with("\r\n") { "\r\n" } else { "\n" };
+                let margin = if block.indent { margin(content) } else { "" };
+                for text_line in text.lines() {
+                    if !text_line.is_empty() {
+                        section.push_str(margin);
+                    }
+                    section.push_str(text_line);
+                    section.push_str(newline);
+                }
+                writer.write_all(section.as_bytes())?;
+            }
+        }
+        writer.flush()?;
+
+        let section_name = block.section_name.clone();
+        let (start, end) = match (start, end) {
+            (Some(start), Some(end)) => (start, end),
+            (None, _) => {
+                let marker = block.start_marker();
+                return Err(ParserError::MarkerNotFound {
+                    section: section_name,
+                    marker,
+                    path: None,
+                });
+            }
+            (_, None) => {
+                let marker = block.end_marker();
+                return Err(ParserError::MarkerNotFound {
+                    section: section_name,
+                    marker,
+                    path: None,
+                });
+            }
+        };
+        if let Some(duplicate) = duplicate_start.or(duplicate_end) {
+            let first = if duplicate_start.is_some() { start } else { end };
+            return Err(ParserError::DuplicateSection {
+                section: section_name,
+                first,
+                duplicate,
+                path: None,
+            });
+        }
+        if end.line <= start.line {
+            return Err(ParserError::MarkersOutOfOrder {
+                section: section_name,
+                start,
+                end,
+                path: None,
+            });
+        }
+
+        Ok(changed || matched != section.len())
+    }
+
+    /// `replace` updates the section of `block` in the file at `path` with `text`.
+    ///
+    /// The file is streamed through [`replace_streaming`] into a temporary file, which replaces
+    /// the original only if the content changed, so memory use doesn't grow with the size of the
+    /// file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file at `path` can't be read or written, or if
+    /// the start or end marker is not found.
+    pub fn replace(text: &str, block: CommentBlock, path: &Path) -> super::Result<()> {
+        let block = block.for_path(path);
+        if let FileStatus::Created = replace_file(text, &block, path, None)? {
+            log::info!("Created section `{}` in {}", block.section_name, path.display());
+        }
+
+        Ok(())
+    }
+
+    /// `replace_with_backups` is [`replace`], saving the current version of the file in `backups`
+    /// before it is overwritten. Nothing is saved if the section is already up to date.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error for the same reasons as [`replace`], or if the backup
+    /// can't be saved, in which case the file is left untouched.
+    pub fn replace_with_backups(
+        text: &str,
+        block: CommentBlock,
+        path: &Path,
+        backups: &Backups,
+    ) -> super::Result<()> {
+        let block = block.for_path(path);
+        if let FileStatus::Created = replace_file(text, &block, path, Some(backups))? {
+            log::info!("Created section `{}` in {}", block.section_name, path.display());
+        }
+
+        Ok(())
+    }
+
+    /// Same as [`replace`], returning whether the file was updated, left unchanged, or had the
+    /// section created. `block` is used as is.
+    pub(crate) fn replace_file(
+        text: &str,
+        block: &CommentBlock,
+        path: &Path,
+        backups: Option<&Backups>,
+    ) -> super::Result<FileStatus> {
+        replace_file_with(block, path, backups, || Ok(text.to_string()))
+    }
+
+    /// Same as [`replace_file`], with the text returned by `render`, which runs under the lock of
+    /// the file so it can read the file without another update slipping in before the write.
+    pub(crate) fn replace_file_with<F>(
+        block: &CommentBlock,
+        path: &Path,
+        backups: Option<&Backups>,
+        render: F,
+    ) -> super::Result<FileStatus>
+    where
+        F: FnOnce() -> super::Result<String>,
+    {
+        // Swap the updated content in for `README.md` without ever leaving it missing. The file is
+        // read within the update, so it stays locked from the read to the write.
+        let mut created = false;
+        let changed = write_atomic_with(path, backups, |writer| {
+            let text = &render()?;
+            if block.insert == Insert::Never {
+                let reader =
+                    BufReader::new(fs::File::open(path).map_err(|e| ParserError::Io(Arc::new(e)))?);
+                return replace_streaming(text, block, reader, writer);
+            }
+            let replaced = replace_in_str(text, block, &read_file(path)?)?;
+            writer.write_all(replaced.content.as_bytes())?;
+            created = replaced.created;
+            Ok(replaced.changed)
+        })
+        .map_err(|e| e.with_path(path))?;
+
+        Ok(match (created, changed) {
+            (true, _) => FileStatus::Created,
+            (false, true) => FileStatus::Updated,
+            (false, false) => FileStatus::Unchanged,
+        })
+    }
+
+    /// `replace_par` is kept for callers of the parallel version and behaves like [`replace`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error for the same reasons as [`replace`].
+    pub fn replace_par(text: &str, block: CommentBlock, path: &Path) -> super::Result<()> {
+        replace(text, block, path)
+    }
+
+    /// `replace_many` updates every requested section of the file at `path` in one pass.
+    ///
+    /// The file is read once, all start and end markers are located in a single scan over its
+    /// lines, each section is spliced with its new text, and the result is written back once.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error, without writing to the file, if:
+    ///
+    /// * A section is requested more than once, with [`ParserError::SectionRequestedTwice`].
+    /// * The start or end marker of any requested section is not found.
+    /// * A start marker comes after its end marker, or two sections overlap.
+    pub fn replace_many(sections: &[(CommentBlock, String)], path: &Path) -> super::Result<()> {
+        write_atomic_with(path, None, |writer| {
+            let replaced =
+                replace_many_in_str(&sections_for_path(sections, path), &read_file(path)?)?;
+            writer.write_all(replaced.content.as_bytes())?;
+            Ok(replaced.changed)
+        })
+        .map_err(|e| e.with_path(path))?;
+
+        Ok(())
+    }
+
+    /// `replace_dry_run` reports how [`replace`] would change the file at `path`, without writing
+    /// to it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error for the same reasons as [`replace`].
+    pub fn replace_dry_run(
+        text: &str,
+        block: &CommentBlock,
+        path: &Path,
+    ) -> super::Result<ReplaceOutcome> {
+        let buf = read_file(path)?;
+        let replaced = replace_in_str(text, &block.clone().for_path(path), &buf)
+            .map_err(|e| e.with_path(path))?;
+
+        Ok(ReplaceOutcome::new(&buf, &replaced))
+    }
+
+    /// `replace_many_dry_run` reports how [`replace_many`] would change the file at `path`,
+    /// without writing to it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error for the same reasons as [`replace_many`].
+    pub fn replace_many_dry_run(
+        sections: &[(CommentBlock, String)],
+        path: &Path,
+    ) -> super::Result<ReplaceOutcome> {
+        let buf = read_file(path)?;
+        let replaced = replace_many_in_str(&sections_for_path(sections, path), &buf)
+            .map_err(|e| e.with_path(path))?;
+
+        Ok(ReplaceOutcome::new(&buf, &replaced))
+    }
+
+    /// `check` verifies that the section of `block` in the file at `path` is up to date with
+    /// `text`, like `cargo fmt --check`. The file is never written.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file can't be read, or for the same reasons as
+    /// [`check_in_str`].
+    pub fn check(text: &str, block: &CommentBlock, path: &Path) -> super::Result<()> {
+        check_in_str(text, &block.clone().for_path(path), &read_file(path)?)
+            .map_err(|e| e.with_path(path))
+    }
+
+    /// `section_attributes_in_str` returns the attributes written in the start marker of the
+    /// section of `block` in `buf`, e.g. `sort=stars limit=10` in
+    /// `<!--START_SECTION:repos sort=stars limit=10-->`.
+    ///
+    /// If `buf` has no markers of the section and `block` would create them, the attributes of
+    /// `block` are returned, as those are the ones the markers will be created with.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the markers of the section are not found, or if the
+    /// attributes are malformed.
+    pub fn section_attributes_in_str(block: &CommentBlock, buf: &str) -> super::Result<Attributes> {
+        if block.insert != Insert::Never && !has_markers(buf, block)? {
+            return Ok(block.attributes.clone());
+        }
+        let (n_start, _) = block_positions(buf, block)?;
+        let line = buf.lines().nth(n_start).unwrap_or_default();
+        let re_start = Regex::new(&block.marker_pattern(&block.marker.0))?;
+        let raw = re_start.captures(line).and_then(|caps| caps.name("attrs"));
+
+        parse_attributes(raw.map_or("", |m| m.as_str()))
+    }
+
+    /// `section_attributes` returns the attributes of the section of `block` in the file at
+    /// `path`. See [`section_attributes_in_str`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file can't be read, or for the same reasons as
+    /// [`section_attributes_in_str`].
+    pub fn section_attributes(block: &CommentBlock, path: &Path) -> super::Result<Attributes> {
+        section_attributes_in_str(&block.clone().for_path(path), &read_file(path)?)
+            .map_err(|e| e.with_path(path))
+    }
+
+    /// `extract` returns the current content of the section of `block` in `buf`, the lines between
+    /// its markers joined by `\n`. The margin of an indented block is removed, so replacing the
+    /// section with the extracted content leaves `buf` unchanged.
+    ///
+    /// ```rust
+    /// use parser::*;
+    ///
+    /// let buf = "<!--START_SECTION:tag_1-->\n* a\n* b\n<!--END_SECTION:tag_1-->\n";
+    /// let block = CommentBlock::new("tag_1".to_string());
+    ///
+    /// assert_eq!(extract(&block, buf).unwrap(), "* a\n* b");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the markers of the section are not found or are
+    /// malformed.
+    pub fn extract(block: &CommentBlock, buf: &str) -> super::Result<String> {
+        let (n_start, n_end) = block_positions(buf, block)?;
+
+        Ok(content_lines(buf, (n_start, n_end), block).join("\n"))
+    }
+
+    /// `extract_file` returns the current content of the section of `block` in the file at
+    /// `path`. See [`extract`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file can't be read, or for the same reasons as
+    /// [`extract`].
+    pub fn extract_file(block: &CommentBlock, path: &Path) -> super::Result<String> {
+        extract(&block.clone().for_path(path), &read_file(path)?).map_err(|e| e.with_path(path))
+    }
+
+    /// Returns `sections` with the comment style of each block picked from `path`, unless one was
+    /// set explicitly.
+    fn sections_for_path(
+        sections: &[(CommentBlock, String)],
+        path: &Path,
+    ) -> Vec<(CommentBlock, String)> {
+        sections.iter().map(|(block, text)| (block.clone().for_path(path), text.clone())).collect()
+    }
+
+    /// Returns the line positions of the start and end markers of `block` in `buf`.
+    fn block_positions(buf: &str, block: &CommentBlock) -> super::Result<(usize, usize)> {
+        let (start, end) = get_many_block_positions(buf, &[block])?[0];
+
+        Ok((start.line, end.line))
+    }
+
+    /// Copies the file at `path` into a buffer.
+    pub(crate) fn read_file(path: &Path) -> super::Result<String> {
+        let mut buf = String::new();
+        fs::File::open(path)
+            .map_err(|e| ParserError::Io(Arc::new(e)))?
+            .read_to_string(&mut buf)
+            .map_err(|e| ParserError::Io(Arc::new(e)))?;
+
+        Ok(buf)
+    }
+
+    /// Replaces the lines strictly between each `(n_start, n_end)` pair of `edits` with the lines
+    /// of its text, indented like the start marker if asked to. `edits` must be sorted and must
+    /// not overlap.
+    ///
+    /// Lines outside the sections are kept byte for byte, so a byte order mark, CRLF line endings
+    /// and the presence or absence of a final newline survive the splice. Inserted lines end like
+    /// the start marker of their section.
+    fn splice(buf: &str, edits: &[(usize, usize, &str, bool)]) -> Replaced {
+        let buf_arr: Vec<&str> = buf.split_inclusive('\n').collect();
+        let mut content = String::with_capacity(buf.len());
+        let mut cursor = 0;
+        for &(n_start, n_end, text, indent) in edits {
+            buf_arr[cursor..=n_start].iter().for_each(|line| content.push_str(line));
+            let start = buf_arr[n_start];
+            let newline = if start.ends_with("\r\n") { "\r\n" } else { "\n" };
+            let margin = if indent { margin(start) } else { "" };
+            for line in text.lines() {
+                if !line.is_empty() {
+                    content.push_str(margin);
+                }
+                content.push_str(line);
+                content.push_str(newline);
+            }
+            cursor = n_end;
+        }
+        buf_arr[cursor..].iter().for_each(|line| content.push_str(line));
+
+        let changed = content != buf;
+        Replaced { content, changed, created: false }
+    }
+
+    /// Returns the tree of the section starting at line `start`, searching nested sections too.
+    fn find_section_tree(trees: &[SectionTree], start: usize) -> Option<&SectionTree> {
+        trees.iter().find_map(|tree| match tree.section.start == start {
+            true => Some(tree),
+            false => find_section_tree(&tree.children, start),
+        })
+    }
+
+    /// Returns `text` with the pinned lines between the lines `n_start..n_end` of `buf` merged in,
+    /// if `block` keeps them. See [`Merge`].
+    fn with_merge<'a>(
+        buf: &str,
+        (n_start, n_end): (usize, usize),
+        block: &CommentBlock,
+        text: &'a str,
+    ) -> Cow<'a, str> {
+        if block.merge == Merge::Replace {
+            return Cow::Borrowed(text);
+        }
+        let old = content_lines(buf, (n_start, n_end), block).join("\n");
+
+        Cow::Owned(merge_pinned(&old, text, &block.style()))
+    }
+
+    /// Returns `buf` with the hash of `text` stamped in the end marker of `block`, if `block` asks
+    /// for it, once the current content between the markers at `start` and `end` has been
+    /// checked against the hash stamped before. See [`Stamp`].
+    fn with_stamp<'a>(
+        buf: &'a str,
+        (start, end): (Span, Span),
+        block: &CommentBlock,
+        text: &str,
+    ) -> super::Result<Cow<'a, str>> {
+        if block.stamp == Stamp::Off {
+            return Ok(Cow::Borrowed(buf));
+        }
+        let lines: Vec<&str> = buf.split_inclusive('\n').collect();
+        let line = lines[end.line];
+        let re_end = Regex::new(&block.marker_pattern(&block.marker.1))?;
+        let caps = re_end.captures(line).ok_or_else(|| {
+            ParserError::LogicBug(format!("end marker of `{}` not found", block.section_name))
+        })?;
+        let mut attrs = parse_attributes(caps.name("attrs").map_or("", |m| m.as_str()))?;
+
+        let found = content_hash(&content_lines(buf, (start.line, end.line), block).join("\n"));
+        match attrs.get(HASH) {
+            Some(stamped) if *stamped != found && block.stamp == Stamp::Refuse => {
+                return Err(ParserError::SectionEdited {
+                    section: block.section_name.clone(),
+                    end,
+                    stamped: stamped.clone(),
+                    path: None,
+                });
+            }
+            Some(stamped) if *stamped != found => log::warn!(
+                "Section `{}` was edited since it was generated, overwriting it",
+                block.section_name
+            ),
+            _ => (),
+        }
+
+        attrs.insert(HASH.to_string(), content_hash(&text.lines().collect::<Vec<_>>().join("\n")));
+        let head = block.format.keyword(&block.marker.1, &block.section_name);
+        let marker = block.style().wrap(&format!("{head} {}", fmt_attributes(&attrs)));
+        let range = caps.get(0).map_or(0..0, |m| m.range());
+        if line[range.clone()] == marker {
+            return Ok(Cow::Borrowed(buf));
+        }
+        let mut stamped = String::with_capacity(buf.len() + marker.len());
+        lines[..end.line].iter().for_each(|line| stamped.push_str(line));
+        stamped.push_str(&line[..range.start]);
+        stamped.push_str(&marker);
+        stamped.push_str(&line[range.end..]);
+        lines[end.line + 1..].iter().for_each(|line| stamped.push_str(line));
+
+        Ok(Cow::Owned(stamped))
+    }
+
+    /// Returns the short hash of the content of a section stamped by [`Stamp`], the 32-bit FNV-1a
+    /// hash of its lines joined with `\n`, in hex.
+    fn content_hash(content: &str) -> String {
+        let hash = content
+            .bytes()
+            .fold(0x811c_9dc5_u32, |hash, byte| (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193));
+
+        format!("{hash:08x}")
+    }
+
+    /// Returns the lines strictly between the lines `n_start` and `n_end` of `buf`, without the
+    /// margin of the start marker if `block` indents its content.
+    fn content_lines<'a>(
+        buf: &'a str,
+        (n_start, n_end): (usize, usize),
+        block: &CommentBlock,
+    ) -> Vec<&'a str> {
+        let mut lines = buf.lines().skip(n_start);
+        let margin = match (block.indent, lines.next()) {
+            (true, Some(start)) => margin(start),
+            _ => "",
+        };
+
+        lines
+            .take(n_end - n_start - 1)
+            .map(|line| line.strip_prefix(margin).unwrap_or(line))
+            .collect()
     }
 
-    /// `impl CommentBlock` is an implementation block for the CommentBlock struct.
-    ///
-    /// It provides a constructor new for creating new CommentBlock values, and two methods:
-    /// `start_marker` and `end_marker`, which return the start and end markers as `String` values.
-    impl CommentBlock {
-        /// Creates a new [`CommentBlock`].
-        pub fn new(section_name: String) -> Self {
-            Self {
-                section_name: section_name.trim().to_string(),
-                marker: (Marker::Start, Marker::End),
-            }
+    /// Returns `text` with the current content of every section directly inside the lines
+    /// `n_start..n_end` of `buf` carried over, if `block` preserves its inner sections.
+    fn with_nested<'a>(
+        buf: &str,
+        (n_start, n_end): (usize, usize),
+        block: &CommentBlock,
+        text: &'a str,
+    ) -> super::Result<Cow<'a, str>> {
+        if block.nested == Nested::Regenerate {
+            return Ok(Cow::Borrowed(text));
+        }
+
+        let style = block.style();
+        let scan = scan_sections_with_format(buf, &style, &block.format);
+        let problems: Vec<SectionProblem> = scan
+            .problems
+            .iter()
+            .filter(|p| (n_start..=n_end).contains(&p.line()))
+            .cloned()
+            .collect();
+        if !problems.is_empty() {
+            return Err(ParserError::InvalidSections { problems, path: None });
         }
-        // fn start_marker(&self) -> String { format!("<!--START_SECTION:{}-->", self.section_name)
-        // } fn end_marker(&self) -> String { format!("<!--END_SECTION:{}-->", self.section_name) }
+        let tree = scan.tree();
+        let children = find_section_tree(&tree, n_start).map_or(&[][..], |tree| &tree.children[..]);
+
+        let inner: Vec<CommentBlock> = children
+            .iter()
+            .map(|child| {
+                CommentBlock::new(child.section.name.clone())
+                    .with_style(style.clone())
+                    .with_format(block.format.clone())
+            })
+            .collect();
+        let positions = get_many_block_positions(text, &inner.iter().collect::<Vec<_>>()).map_err(
+            |e| match e {
+                ParserError::MarkerNotFound { section: inner, .. } => {
+                    ParserError::NestedSectionDropped { section: block.section_name.clone(), inner }
+                }
+                e => e,
+            },
+        )?;
+
+        // Without the margin of an indented block, which the splice puts back, so preserved lines
+        // are not indented twice.
+        let lines = content_lines(buf, (n_start, n_end), block);
+        let contents: Vec<String> = children
+            .iter()
+            .map(|child| {
+                lines[child.section.start - n_start..child.section.end - n_start - 1].join("\n")
+            })
+            .collect();
+        let mut edits: Vec<(usize, usize, &str, bool)> = positions
+            .iter()
+            .zip(&contents)
+            .map(|((start, end), content)| (start.line, end.line, content.as_str(), false))
+            .collect();
+        edits.sort_by_key(|&(n_start, ..)| n_start);
+
+        Ok(Cow::Owned(splice(text, &edits).content))
     }
 
-    /// `replace` function first opens the file and reads its contents into a string buffer.
-    // It then creates a CommentBlock struct with a specified section name and generates the start
-    // and end markers from it. The function then finds the positions of the start and end
-    // markers in the buffer using the position method. It removes the content between the
-    // markers using the clear method, inserts the updated content using the splice method, and
-    // updates the file with the new content.
+    /// `write_atomic` replaces the contents of the file at `path` with `contents`.
     ///
-    /// # Panics
+    /// The content is written to a temporary file in the same directory, flushed to disk, and then
+    /// renamed over the original, so an interrupted run leaves either the old or the new file in
+    /// place and never a missing or truncated one. Symlinks are followed, so the link is kept and
+    /// its target is updated, and the original permissions are carried over to the new file.
     ///
-    /// * File at `path` doesn't exist.
-    /// * start or end marker are not found'.
-    /// * `path` being passed to the replace function. - Instead of passing input.path which is a
-    ///   Path object, you should pass &path, which is the path to the tempfile as a string slice.
+    /// Concurrent updates are serialized with an advisory [`FileLock`], and the file is left
+    /// untouched with [`ParserError::ConcurrentModification`] if something else changed it since
+    /// the lock was taken. The current version is saved in `backups` under the same lock.
+    pub(crate) fn write_atomic(
+        path: &Path,
+        contents: &[u8],
+        backups: Option<&Backups>,
+    ) -> super::Result<()> {
+        write_atomic_with(path, backups, |writer| {
+            writer.write_all(contents)?;
+            Ok(true)
+        })?;
+
+        Ok(())
+    }
+
+    /// Like [`write_atomic`], with the new contents written by `write`, which returns whether
+    /// they differ from the current ones. The file is left untouched if they don't, or if `write`
+    /// fails. The current version is saved in `backups` right before it is replaced.
     ///
-    /// # Errors
+    /// `write` runs under the lock, so it should read the current contents itself.
     ///
-    /// This function will return an error if it panics.
-    //
-    // PERF: Create regex to find all `section_names` from copied `README.md`.
-    pub fn replace(text: &str, block: CommentBlock, path: &Path) -> super::Result<()> {
-        // Find the start and end of sections surrounded with comment block.
-        let (re_start, re_end) = rayon::join(
-            || comment_block!(block.section_name, block.marker.0),
-            || comment_block!(block.section_name, block.marker.1),
-        );
+    /// Returns the value returned by `write`.
+    fn write_atomic_with<F>(path: &Path, backups: Option<&Backups>, write: F) -> super::Result<bool>
+    where
+        F: FnOnce(&mut BufWriter<&mut tempfile::NamedTempFile>) -> super::Result<bool>,
+    {
+        let target = fs::canonicalize(path)?;
+        let dir = target.parent().ok_or_else(|| {
+            ParserError::LogicBug(format!("{} has no parent directory", target.display()))
+        })?;
+        let permissions = fs::metadata(&target)?.permissions();
+        let _lock = FileLock::acquire(&target)?;
+        let snapshot = Snapshot::take(&target)?;
 
-        // First copy the existing file into a buffer.
-        let mut buf = String::new();
-        fs::File::open(path)
-            .map_err(|e| ParserError::Io(Arc::new(e)))?
-            .read_to_string(&mut buf)
-            .map_err(|e| ParserError::Io(Arc::new(e)))?;
-        log::debug!("Read and copied file:\n>> {}\n```\n{buf}\n```", path.display());
-
-        // Returns the start and end position of regex section.
-        let (n_start, n_end) = get_block_positions(&buf, &re_start, &re_end)
-            .map_err(|e| ParserError::RegexError(e.into()))?;
-
-        // Split content into lines and update the section
-        let buf_arr: Vec<&str> = buf.lines().collect();
-        let mut start = buf_arr[0..=n_start].to_owned();
-        let mut end = buf_arr[n_end..].to_owned();
-        let mut middle = text.lines().collect();
-        // PERF: This can be highly made effecient.
-        // Join start, updated middle, and end with new line.
-        start.append(&mut middle);
-        end.append(&mut vec!["\n"]);
-        start.append(&mut end);
-        let updated_content: String = start.join("\n");
-
-        // Remove the original file `README.md`
-        fs::remove_file(path).map_err(|e| ParserError::Io(Arc::new(e)))?;
-
-        // Create a new file and write the updated content: Write all to new README.md.
-        fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(path)
-            .map_err(|e| ParserError::Io(e.into()))?
-            .write_all(updated_content.as_bytes())
-            .map_err(|e| ParserError::Io(e.into()))?;
+        let mut tmp =
+            tempfile::Builder::new().prefix(".findrepl-").suffix(".tmp").tempfile_in(dir)?;
+        let mut writer = BufWriter::new(&mut tmp);
+        let changed = write(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        if !changed {
+            return Ok(false);
+        }
+        if Snapshot::take(&target)? != snapshot {
+            return Err(ParserError::ConcurrentModification { path: path.to_path_buf() });
+        }
+        if let Some(backups) = backups {
+            backups.save(path)?;
+        }
+        tmp.as_file().set_permissions(permissions)?;
+        tmp.as_file().sync_all()?;
+        tmp.persist(&target).map_err(|e| ParserError::Io(Arc::new(e.error)))?;
 
-        Ok(())
+        // Make the rename itself durable.
+        #[cfg(unix)]
+        fs::File::open(dir)?.sync_all()?;
+
+        Ok(true)
     }
 
-    /// This version uses `rayon::par_iter` to bring the necessary traits into scope.
-    /// Then, it chains the start, middle and end parts of the original string together using the
-    /// `.par_iter()` method, which enables parallel processing.
-    /// Finally, it collects the results into a vector of strings and joins them as a single string.
-    pub fn replace_par(text: &str, block: CommentBlock, path: &Path) -> super::Result<()> {
-        // Find the start and end of sections surrounded with comment block.
-        let (re_start, re_end) = rayon::join(
-            || comment_block!(block.section_name, block.marker.0),
-            || comment_block!(block.section_name, block.marker.1),
-        );
+    /// Returns `buf` with empty markers of `block` inserted where its [`Insert`] policy says, or
+    /// `None` if the policy is [`Insert::Never`] or `buf` already has a marker of the section.
+    fn with_markers(buf: &str, block: &CommentBlock) -> super::Result<Option<String>> {
+        if block.insert == Insert::Never || has_markers(buf, block)? {
+            return Ok(None);
+        }
+        let newline = if buf.contains("\r\n") { "\r\n" } else { "\n" };
+        let markers = format!("{}{newline}{}{newline}", block.start_marker(), block.end_marker());
+        let not_found = |target: String| ParserError::InsertionPointNotFound {
+            section: block.section_name.clone(),
+            target,
+        };
 
-        // First copy the existing file into a buffer.
-        let mut buf = String::new();
-        fs::File::open(path)
-            .map_err(|e| ParserError::Io(Arc::new(e)))?
-            .read_to_string(&mut buf)
-            .map_err(|e| ParserError::Io(Arc::new(e)))?;
+        // Byte offset of each line outside of code blocks, with the line.
+        let code = code_block_lines(buf, &block.style());
+        let mut offset = 0;
+        let mut lines = buf.split_inclusive('\n').zip(code).filter_map(|(line, is_code)| {
+            offset += line.len();
+            (!is_code).then_some((offset - line.len(), line))
+        });
+        let (at, insertion) = match &block.insert {
+            Insert::Never => return Ok(None),
+            Insert::Append => {
+                let trailing = &buf[buf.trim_end_matches(['\r', '\n']).len()..];
+                let blank = match (buf.is_empty(), trailing.matches('\n').count()) {
+                    (true, _) | (_, 2..) => String::new(),
+                    (_, n) => newline.repeat(2 - n),
+                };
+                (buf.len(), format!("{blank}{markers}"))
+            }
+            Insert::AfterHeading(heading) => {
+                let heading = heading.trim();
+                let is_heading = |line: &str| {
+                    let line = line.trim();
+                    line.starts_with('#')
+                        && match heading.starts_with('#') {
+                            true => line == heading,
+                            false => line.trim_start_matches('#').trim() == heading,
+                        }
+                };
+                let (start, line) = lines
+                    .find(|(_, line)| is_heading(line))
+                    .ok_or_else(|| not_found(format!("heading `{heading}`")))?;
+                let end_of_line = if line.ends_with('\n') { "" } else { newline };
+                (start + line.len(), format!("{end_of_line}{newline}{markers}"))
+            }
+            Insert::BeforeSection(other) => {
+                let other_block = CommentBlock::new(other.clone())
+                    .with_style(block.style())
+                    .with_format(block.format.clone());
+                let re = Regex::new(&other_block.marker_pattern(&Marker::Start))?;
+                let (start, _) = lines
+                    .find(|(_, line)| re.is_match(line))
+                    .ok_or_else(|| not_found(format!("section `{other}`")))?;
+                (start, format!("{markers}{newline}"))
+            }
+        };
 
-        // Returns the start and end position of regex section.
-        let (n_start, n_end) = get_block_positions(&buf, &re_start, &re_end)
-            .map_err(|e| ParserError::RegexError(e.into()))?;
-
-        // Split content into lines.
-        let buf_arr: Vec<_> = buf.lines().collect();
-
-        // Combine the start, middle, and end into a single string.
-        let text: String = buf_arr[0..=n_start]
-            .par_iter()
-            .chain(&text.lines().collect::<Vec<_>>())
-            .chain(&buf_arr[n_end..])
-            .map(ToOwned::to_owned)
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        // Remove the original file `README.md` as we will be writing to a new one.
-        // If the next OpenOptions write function overwrites the path? is this useful?
-        crossbeam::scope(|_| {
-            fs::remove_file(path).map_err(|e| ParserError::Io(Arc::new(e))).unwrap()
-        })
-        .map_err(|e| ParserError::ChannelError(anyhow!("{e:?}").to_string()))?;
+        Ok(Some(format!("{}{insertion}{}", &buf[..at], &buf[at..])))
+    }
 
-        // Create a new file and write the updated content: Write all to new README.md.
-        fs::OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(path)
-            .map_err(|e| ParserError::Io(e.into()))?
-            .write_all(text.as_bytes())
-            .map_err(|e| ParserError::Io(e.into()))?;
+    /// Returns the leading whitespace of `line`, after any byte order mark.
+    fn margin(line: &str) -> &str {
+        let line = line.trim_start_matches('\u{feff}');
+        &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+    }
 
-        Ok(())
+    /// Returns `true` if `buf` has a start or end marker of `block` outside of code blocks.
+    pub(crate) fn has_markers(buf: &str, block: &CommentBlock) -> super::Result<bool> {
+        let re_start = Regex::new(&block.marker_pattern(&block.marker.0))?;
+        let re_end = Regex::new(&block.marker_pattern(&block.marker.1))?;
+        let code = code_block_lines(buf, &block.style());
+
+        Ok(buf
+            .lines()
+            .zip(code)
+            .any(|(line, is_code)| !is_code && (re_start.is_match(line) || re_end.is_match(line))))
+    }
+
+    /// Returns the spans of the start and end markers for each of `blocks`, in the same order,
+    /// found with a single scan over the lines of `buf`.
+    fn get_many_block_positions(
+        buf: &str,
+        blocks: &[&CommentBlock],
+    ) -> super::Result<Vec<(Span, Span)>> {
+        let markers = blocks
+            .iter()
+            .map(|b| {
+                b.validate_name()?;
+                let re_start = Regex::new(&b.marker_pattern(&b.marker.0))?;
+                let re_end = Regex::new(&b.marker_pattern(&b.marker.1))?;
+                Ok((re_start, re_end))
+            })
+            .collect::<super::Result<Vec<(Regex, Regex)>>>()?;
+        for (i, block) in blocks.iter().enumerate() {
+            if blocks[..i].iter().any(|b| b.section_name == block.section_name) {
+                return Err(ParserError::SectionRequestedTwice(block.section_name.clone()));
+            }
+        }
+
+        // Every start and end marker of each block outside of code blocks, which only Markdown
+        // has.
+        let mut found: Vec<(Vec<Span>, Vec<Span>)> = vec![Default::default(); blocks.len()];
+        let code = code_block_lines(buf, &CommentStyle::Html);
+        for (n, line) in buf.lines().enumerate() {
+            for (((starts, ends), (re_start, re_end)), block) in
+                found.iter_mut().zip(&markers).zip(blocks)
+            {
+                if code[n] && block.style() == CommentStyle::Html {
+                    continue;
+                }
+                starts.extend(re_start.find(line).map(|m| Span::new(n, line, m.range())));
+                ends.extend(re_end.find(line).map(|m| Span::new(n, line, m.range())));
+            }
+        }
+
+        found
+            .into_iter()
+            .zip(blocks)
+            .map(|((starts, ends), block)| {
+                let section = block.section_name.clone();
+                let (start, end) = match (starts.first(), ends.first()) {
+                    (Some(&start), Some(&end)) => (start, end),
+                    (None, _) => {
+                        let marker = block.start_marker();
+                        return Err(ParserError::MarkerNotFound { section, marker, path: None });
+                    }
+                    (_, None) => {
+                        let marker = block.end_marker();
+                        return Err(ParserError::MarkerNotFound { section, marker, path: None });
+                    }
+                };
+                if let Some(&duplicate) = starts.get(1).or_else(|| ends.get(1)) {
+                    let first = if starts.len() > 1 { start } else { end };
+                    return Err(ParserError::DuplicateSection {
+                        section,
+                        first,
+                        duplicate,
+                        path: None,
+                    });
+                }
+                if end.line <= start.line {
+                    return Err(ParserError::MarkersOutOfOrder { section, start, end, path: None });
+                }
+                Ok((start, end))
+            })
+            .collect()
     }
 
-    /// Returns the line positions of start and end markers for the given section in the buffer.
+    /// Returns the line positions of the given start and end markers in the buffer.
+    ///
+    /// The markers are matched literally, so section names may contain regex syntax such as
+    /// `c++` or `v1.0 (beta)`.
     ///
     /// ```rust
     /// use parser::*;
     /// fn try_main() {
-    ///     let re_start = "<!--START_SECTION:tag_1-->";
-    ///     let re_end = "<!--END_SECTION:tag_1-->";
+    ///     let start_marker = "<!--START_SECTION:tag_1-->";
+    ///     let end_marker = "<!--END_SECTION:tag_1-->";
     ///
     ///     let buf: &str = r#"<!--START_SECTION:tag_1-->
     /// * [lorem](https://github.com/username/username) — username's GitHub profile.
@@ -293,32 +1862,49 @@ pub mod findrepl {
     ///   adipisicing minim sint cillum sint consectetur cupidatat. ...
     /// <!--END_SECTION:tag_1-->"#;
     ///
-    ///     let (start, end) = get_block_positions(buf, &re_start, &re_end).unwrap();
+    ///     let (start, end) = get_block_positions(buf, start_marker, end_marker).unwrap();
     ///     assert_eq!((start, end), (0, 5));
     /// }
     /// ```
     pub fn get_block_positions(
         buf: &str,
-        re_start: &str,
-        re_end: &str,
+        start_marker: &str,
+        end_marker: &str,
     ) -> super::Result<(usize, usize)> {
         let (start, end) = rayon::join(
-            || Regex::new(re_start).map_err(ParserError::RegexError),
-            || Regex::new(re_end).map_err(ParserError::RegexError),
+            || Regex::new(&regex::escape(start_marker)).map_err(ParserError::RegexError),
+            || Regex::new(&regex::escape(end_marker)).map_err(ParserError::RegexError),
         );
         let (start, end) = (start?, end?);
-        let (start, end) = rayon::join(|| start.find(buf), || end.find(buf));
-        let (start, end) = (
-            start.ok_or_else(|| Syntax("start marker not found".to_string()))?,
-            end.ok_or_else(|| Syntax("end marker not found".to_string()))?,
-        );
-        crossbeam::scope(|_| assert!(start.start() < end.start())).unwrap();
-        let (start, end) = rayon::join(
-            || buf[..start.start()].lines().count(),
-            || buf[..end.start()].lines().count(),
-        );
-        debug_assert!(start < end);
-        Ok((start, end))
+
+        // Span of the first match outside of code blocks.
+        let code = code_block_lines(buf, &CommentStyle::Html);
+        let find_span = |re: &Regex| {
+            re.find_iter(buf)
+                .map(|m| {
+                    let line_start = buf[..m.start()].rfind('\n').map_or(0, |i| i + 1);
+                    let n = buf[..m.start()].matches('\n').count();
+                    Span::new(n, &buf[line_start..], m.start() - line_start..m.end() - line_start)
+                })
+                .find(|span| !code[span.line])
+        };
+        let missing = |marker: &str| ParserError::MarkerNotFound {
+            section: String::new(),
+            marker: marker.to_string(),
+            path: None,
+        };
+        let (start, end) = rayon::join(|| find_span(&start), || find_span(&end));
+        let (start, end) =
+            (start.ok_or_else(|| missing(start_marker))?, end.ok_or_else(|| missing(end_marker))?);
+        if start.line >= end.line {
+            return Err(ParserError::MarkersOutOfOrder {
+                section: String::new(),
+                start,
+                end,
+                path: None,
+            });
+        }
+        Ok((start.line, end.line))
     }
 
     #[cfg(test)]
@@ -335,6 +1921,7 @@ pub mod findrepl {
         use tempfile::tempdir;
 
         use super::*;
+        use crate::{comment_block, lock::lock_path};
 
         #[derive(Debug, PartialEq, Clone, Default)]
         pub struct Input {
@@ -356,10 +1943,11 @@ pub mod findrepl {
             let mut buf = String::new();
             f.read_to_string(&mut buf).unwrap();
             let path = path.to_string_lossy();
-            Ok(assert_eq!(
+            assert_eq!(
                 buf, initial_content,
-                "Should write `INITIAL_CONTENT` to tempfile at {path}",
-            ))
+                "Should write `INITIAL_CONTENT` to tempfile at {path}"
+            );
+            Ok(())
         }
 
         // Struct representing a block of text
@@ -428,6 +2016,612 @@ Lorem ipsum dolor sit amet, qui minim labore adipisicing minim sint cillum sint
             assert_eq!(buf.contains(&input.text), true);
         }
 
+        #[test]
+        fn should_replace_many() {
+            let initial = format!(
+                "{INITIAL_CONTENT}\n\n<!--START_SECTION:tag_2-->\nstale\n<!--END_SECTION:tag_2-->"
+            );
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("README.md");
+            File::create(&path).unwrap().write_all(initial.as_bytes()).unwrap();
+
+            let sections = [
+                (CommentBlock::new("tag_2".to_string()), "* [g](...) - ...".to_string()),
+                (CommentBlock::new("tag_1".to_string()), TO_UPDATE_WITH.to_string()),
+            ];
+            replace_many(&sections, &path).expect("Should replace every requested section");
+
+            let mut buf = String::new();
+            File::open(&path).unwrap().read_to_string(&mut buf).unwrap();
+            assert!(buf.contains(&format!(
+                "<!--START_SECTION:tag_1-->\n{TO_UPDATE_WITH}\n<!--END_SECTION:tag_1-->"
+            )));
+            assert!(buf.contains(
+                "<!--START_SECTION:tag_2-->\n* [g](...) - ...\n<!--END_SECTION:tag_2-->"
+            ));
+            assert!(!buf.contains("stale"));
+            assert!(buf.contains("# LICENSE"));
+        }
+
+        #[test]
+        fn should_not_write_when_any_section_is_missing() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("README.md");
+            File::create(&path).unwrap().write_all(INITIAL_CONTENT.as_bytes()).unwrap();
+
+            let sections = [
+                (CommentBlock::new("tag_1".to_string()), TO_UPDATE_WITH.to_string()),
+                (CommentBlock::new("missing".to_string()), TO_UPDATE_WITH.to_string()),
+            ];
+            let err = replace_many(&sections, &path).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("Marker `<!--START_SECTION:missing-->` not found in {}", path.display())
+            );
+            test_if_written(&path, INITIAL_CONTENT).expect("Should leave the file untouched");
+
+            let block = CommentBlock::new("tag_1".to_string());
+            let sections = [(block.clone(), "a".to_string()), (block, "b".to_string())];
+            let err = replace_many(&sections, &path).unwrap_err();
+            assert!(
+                matches!(err, ParserError::SectionRequestedTwice(ref s) if s == "tag_1"),
+                "{err:?}"
+            );
+            test_if_written(&path, INITIAL_CONTENT).expect("Should leave the file untouched");
+        }
+
+        #[test]
+        fn should_locate_marker_errors() {
+            let block = CommentBlock::new("tag_1".to_string());
+            let (start, end) = ("<!--START_SECTION:tag_1-->", "<!--END_SECTION:tag_1-->");
+
+            let buf = format!("{end}\n  {start}\n");
+            match replace_in_str("", &block, &buf).unwrap_err() {
+                ParserError::MarkersOutOfOrder { start, end, .. } => assert_eq!(
+                    (start, end),
+                    (Span { line: 1, column: 2, len: 26 }, Span { line: 0, column: 0, len: 24 })
+                ),
+                err => panic!("unexpected error: {err:?}"),
+            }
+
+            let buf = format!("{start}\n{end}\n\n{start}\n{end}\n");
+            match replace_in_str("", &block, &buf).unwrap_err() {
+                ParserError::DuplicateSection { first, duplicate, .. } => {
+                    assert_eq!((first.line, duplicate.line), (0, 3))
+                }
+                err => panic!("unexpected error: {err:?}"),
+            }
+
+            for name in ["a-->b", "a sort=stars"] {
+                let block = CommentBlock::new(name.to_string());
+                let err = replace_in_str("", &block, &buf).unwrap_err();
+                assert!(matches!(err, ParserError::InvalidSectionName { .. }), "{err:?}");
+            }
+        }
+
+        #[test]
+        fn should_replace_in_str_and_report_changes() {
+            let block = CommentBlock::new("tag_1".to_string());
+
+            let replaced = replace_in_str(TO_UPDATE_WITH, &block, INITIAL_CONTENT).unwrap();
+            assert!(replaced.changed);
+            assert!(replaced.content.contains(TO_UPDATE_WITH));
+
+            let again = replace_in_str(TO_UPDATE_WITH, &block, &replaced.content).unwrap();
+            assert!(!again.changed);
+            assert_eq!(again.content, replaced.content);
+        }
+
+        #[test]
+        fn should_replace_from_reader_to_writer() {
+            let block = CommentBlock::new("tag_1".to_string());
+            let mut out = Vec::new();
+
+            let changed =
+                replace_in_reader(TO_UPDATE_WITH, &block, INITIAL_CONTENT.as_bytes(), &mut out)
+                    .unwrap();
+            assert!(changed);
+            assert_eq!(
+                String::from_utf8(out).unwrap(),
+                replace_in_str(TO_UPDATE_WITH, &block, INITIAL_CONTENT).unwrap().content
+            );
+        }
+
+        #[test]
+        fn should_replace_in_any_comment_style() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("Cargo.toml");
+            let initial =
+                "[dependencies]\n# START_SECTION:deps\nold = \"0.1\"\n# END_SECTION:deps\n";
+            File::create(&path).unwrap().write_all(initial.as_bytes()).unwrap();
+
+            replace("new = \"0.2\"", CommentBlock::new("deps".to_string()), &path).unwrap();
+            let mut buf = String::new();
+            File::open(&path).unwrap().read_to_string(&mut buf).unwrap();
+            assert!(buf.contains("# START_SECTION:deps\nnew = \"0.2\"\n# END_SECTION:deps"));
+
+            let block = CommentBlock::new("x".to_string()).with_style(CommentStyle::Block);
+            let buf = "a {}\n/* START_SECTION:x */\n/* END_SECTION:x */";
+            let replaced = replace_in_str("b {}", &block, buf).unwrap();
+            assert_eq!(replaced.content, "a {}\n/* START_SECTION:x */\nb {}\n/* END_SECTION:x */");
+        }
+
+        #[test]
+        fn should_keep_marker_attributes() {
+            let buf = "<!--START_SECTION:tag_10-->\n<!--END_SECTION:tag_10-->\n\
+                       <!--START_SECTION:tag_1 sort=stars title=\"Top 5\"-->\n\
+                       <!--END_SECTION:tag_1-->";
+            let block = CommentBlock::new("tag_1".to_string());
+
+            let replaced = replace_in_str("* [a](...)", &block, buf).unwrap();
+            assert_eq!(
+                replaced.content,
+                "<!--START_SECTION:tag_10-->\n<!--END_SECTION:tag_10-->\n\
+                 <!--START_SECTION:tag_1 sort=stars title=\"Top 5\"-->\n* [a](...)\n\
+                 <!--END_SECTION:tag_1-->"
+            );
+            let attrs = section_attributes_in_str(&block, buf).unwrap();
+            assert_eq!(fmt_attributes(&attrs), "sort=stars title=\"Top 5\"");
+            assert_eq!(
+                block.with_attribute("limit", "10").start_marker(),
+                comment_block!("tag_1", "START", "limit=10")
+            );
+        }
+
+        #[test]
+        fn should_match_markers_literally_and_with_spaces() {
+            for name in ["c++", "v1.0 (beta)", "a|b"] {
+                let block = CommentBlock::new(name.to_string());
+                let buf = format!("<!--START_SECTION:{name}-->\n<!--END_SECTION:{name}-->\n");
+                let replaced = replace_in_str("x", &block, &buf).unwrap();
+                assert!(replaced.content.contains(&format!("-->\nx\n<!--END_SECTION:{name}")));
+                assert!(replace_in_str("x", &block, "<!--START_SECTION:c-->\n").is_err());
+            }
+
+            let block = CommentBlock::new("tag_1".to_string());
+            let buf =
+                "<!--  START_SECTION: tag_1   sort=stars -->\nold\n<!-- END_SECTION:tag_1 -->";
+            let replaced = replace_in_str("new", &block, buf).unwrap();
+            assert_eq!(
+                replaced.content,
+                "<!--  START_SECTION: tag_1   sort=stars -->\nnew\n<!-- END_SECTION:tag_1 -->"
+            );
+            assert_eq!(section_attributes_in_str(&block, buf).unwrap()["sort"], "stars");
+
+            let block = block.with_style(CommentStyle::Hash);
+            let buf = "#START_SECTION:tag_1\nold\n#   END_SECTION:tag_1  \n";
+            assert!(replace_in_str("new", &block, buf).unwrap().content.contains("tag_1\nnew\n#"));
+
+            let err = replace_in_str("", &CommentBlock::new("<!--x".to_string()), buf).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Invalid section name `<!--x`: the name contains the comment delimiter `<!--`"
+            );
+        }
+
+        #[test]
+        fn should_regenerate_or_preserve_nested_sections() {
+            let buf = "<!--START_SECTION:projects-->\n## Rust\n<!--START_SECTION:rust-->\n\
+                       * kept\n<!--END_SECTION:rust-->\n<!--END_SECTION:projects-->\n";
+            let text = "## Rust crates\n<!--START_SECTION:rust-->\n<!--END_SECTION:rust-->";
+            let block = CommentBlock::new("projects".to_string());
+
+            let regenerated = replace_in_str(text, &block, buf).unwrap();
+            assert!(!regenerated.content.contains("* kept"));
+
+            let block = block.with_nested(Nested::Preserve);
+            let preserved = replace_in_str(text, &block, buf).unwrap();
+            assert_eq!(
+                preserved.content,
+                "<!--START_SECTION:projects-->\n## Rust crates\n<!--START_SECTION:rust-->\n\
+                 * kept\n<!--END_SECTION:rust-->\n<!--END_SECTION:projects-->\n"
+            );
+            assert!(check_in_str(text, &block, &preserved.content).is_ok());
+
+            let err = replace_in_str("## Rust", &block, buf).unwrap_err();
+            assert!(
+                matches!(err, ParserError::NestedSectionDropped { ref inner, .. } if inner == "rust")
+            );
+
+            // Preserved lines of an indented block keep their margin, only new lines get one.
+            let buf = "* Projects\n  <!--START_SECTION:projects-->\n  ## Rust\n  \
+                       <!--START_SECTION:rust-->\n    * kept\n  <!--END_SECTION:rust-->\n  \
+                       <!--END_SECTION:projects-->\n";
+            let block = block.with_indent(true);
+            let preserved = replace_in_str(text, &block, buf).unwrap();
+            assert_eq!(preserved.content, buf.replace("## Rust\n", "## Rust crates\n"));
+            assert!(check_in_str(text, &block, &preserved.content).is_ok());
+        }
+
+        #[test]
+        fn should_ignore_markers_in_code_examples() {
+            let buf = "Add this to your markdown file:\n\n```md\n<!--START_SECTION:tag_1-->\n\
+                       <!--END_SECTION:tag_1-->\n```\n\n<!--START_SECTION:tag_1-->\n\
+                       <!--END_SECTION:tag_1-->\n";
+            let block = CommentBlock::new("tag_1".to_string());
+
+            let replaced = replace_in_str("* [a](...)", &block, buf).unwrap();
+            assert!(replaced.content.starts_with(&buf[..buf.find("```\n\n").unwrap()]));
+            assert!(replaced
+                .content
+                .ends_with("<!--START_SECTION:tag_1-->\n* [a](...)\n<!--END_SECTION:tag_1-->\n"));
+        }
+
+        #[test]
+        fn should_find_indented_markers_outside_of_markdown() {
+            let yaml = "jobs:\n  build:\n\n    # START_SECTION:steps\n    - run: old\n    \
+                        # END_SECTION:steps\n";
+            let block = CommentBlock::new("steps".to_string()).with_style(CommentStyle::Hash);
+            let replaced = replace_in_str("- run: new", &block, yaml).unwrap();
+            assert_eq!(
+                replaced.content,
+                "jobs:\n  build:\n\n    # START_SECTION:steps\n- run: new\n    \
+                 # END_SECTION:steps\n"
+            );
+
+            let rust = "fn main() {\n    let a = 1;\n\n    // START_SECTION:gen\n    old();\n    \
+                        // END_SECTION:gen\n}\n";
+            let block = CommentBlock::new("gen".to_string()).with_style(CommentStyle::DoubleSlash);
+            assert!(has_markers(rust, &block).unwrap());
+            let mut out = Vec::new();
+            assert!(replace_streaming("new();", &block, rust.as_bytes(), &mut out).unwrap());
+            assert_eq!(
+                String::from_utf8(out).unwrap(),
+                replace_in_str("new();", &block, rust).unwrap().content
+            );
+            let blocks = get_many_block_positions(rust, &[&block]).unwrap();
+            assert_eq!(blocks.len(), 1);
+        }
+
+        #[test]
+        fn should_preserve_line_endings_bom_and_indentation() {
+            let block = CommentBlock::new("tag_1".to_string());
+            let buf =
+                "\u{feff}# Title\r\n<!--START_SECTION:tag_1-->\r\nold\r\n<!--END_SECTION:tag_1-->";
+            let replaced = replace_in_str("a\nb", &block, buf).unwrap();
+            assert_eq!(
+                replaced.content,
+                "\u{feff}# Title\r\n<!--START_SECTION:tag_1-->\r\na\r\nb\r\n<!--END_SECTION:tag_1-->"
+            );
+            assert!(check_in_str("a\nb", &block, &replaced.content).is_ok());
+
+            let buf = "* Projects\n  <!--START_SECTION:tag_1-->\n  <!--END_SECTION:tag_1-->\n";
+            let replaced = replace_in_str("* a\n\n* b", &block.with_indent(true), buf).unwrap();
+            assert_eq!(
+                replaced.content,
+                "* Projects\n  <!--START_SECTION:tag_1-->\n  * a\n\n  * b\n  <!--END_SECTION:tag_1-->\n"
+            );
+        }
+
+        #[test]
+        fn should_not_panic_on_a_bom_only_buffer() {
+            let block = CommentBlock::new("tag_1".to_string());
+            let buf = "\u{feff}";
+
+            assert!(scan_sections(buf).sections.is_empty());
+            assert!(!has_markers(buf, &block).unwrap());
+            let err = get_many_block_positions(buf, &[&block]).unwrap_err();
+            assert!(matches!(err, ParserError::MarkerNotFound { .. }), "{err:?}");
+            let err = get_block_positions(buf, &block.start_marker(), &block.end_marker());
+            assert!(err.is_err());
+            assert!(replace_in_str("* a", &block, buf).is_err());
+        }
+
+        #[test]
+        fn should_stream_like_replace_in_str() {
+            let block = CommentBlock::new("tag_1".to_string());
+            let (start, end) = ("<!--START_SECTION:tag_1-->", "<!--END_SECTION:tag_1-->");
+            let unchanged = replace_in_str(TO_UPDATE_WITH, &block, INITIAL_CONTENT).unwrap();
+            let cases = [
+                (block.clone(), INITIAL_CONTENT.to_string()),
+                (block.clone(), unchanged.content),
+                (block.clone(), format!("\u{feff}# Title\r\n{start}\r\nold\r\n{end}")),
+                (block.clone().with_indent(true), format!("* a\n  {start}\n  * b\n  {end}\n")),
+                (block.clone(), format!("```\n{start}\n{end}\n```\n{start}\n{end}\n")),
+                (block.clone(), format!("{start}\n")),
+                (block.clone(), format!("{end}\n{start}\n")),
+                (block.clone(), format!("{start}{end}\n")),
+                (block.clone(), format!("{start}\n{end}\n{start}\n{end}\n")),
+                (block.clone(), format!("{start}\n{start}\n{end}\n")),
+                (
+                    block.clone().with_merge(Merge::KeepPinned),
+                    format!("{start}\nnote <!-- keep -->\n{end}\n"),
+                ),
+                (block.clone().with_stamp(Stamp::Warn), format!("{start}\r\nold\r\n{end}\r\n")),
+            ];
+
+            for (block, buf) in cases {
+                let mut out = Vec::new();
+                let streamed = replace_streaming(TO_UPDATE_WITH, &block, buf.as_bytes(), &mut out);
+                match (replace_in_str(TO_UPDATE_WITH, &block, &buf), streamed) {
+                    (Ok(replaced), Ok(changed)) => {
+                        assert_eq!(String::from_utf8(out).unwrap(), replaced.content);
+                        assert_eq!(changed, replaced.changed, "{buf}");
+                    }
+                    (Err(expected), Err(err)) => {
+                        assert_eq!(format!("{err:?}"), format!("{expected:?}"))
+                    }
+                    (expected, streamed) => panic!("{buf}: {expected:?} != {streamed:?}"),
+                }
+            }
+        }
+
+        #[test]
+        fn should_keep_pinned_lines_when_merging() {
+            let block = CommentBlock::new("tag_1".to_string()).with_merge(Merge::KeepPinned);
+            let buf = "\
+* Projects
+  <!--START_SECTION:tag_1-->
+  * [a](https://github.com/u/a)
+    Moved to u/b. <!-- keep -->
+  <!--END_SECTION:tag_1-->
+";
+            let text = "* [b](https://github.com/u/b)\n* [a](https://github.com/u/a) — new";
+            let expected = "\
+* Projects
+  <!--START_SECTION:tag_1-->
+  * [b](https://github.com/u/b)
+  * [a](https://github.com/u/a) — new
+    Moved to u/b. <!-- keep -->
+  <!--END_SECTION:tag_1-->
+";
+            let block = block.with_indent(true);
+            assert_eq!(replace_in_str(text, &block, buf).unwrap().content, expected);
+            let sections = [(block.clone(), text.to_string())];
+            assert_eq!(replace_many_in_str(&sections, buf).unwrap().content, expected);
+
+            let replaced = replace_in_str(text, &block.with_merge(Merge::Replace), buf).unwrap();
+            assert!(!replaced.content.contains("keep"));
+        }
+
+        #[test]
+        fn should_stamp_content_hash_and_detect_edits() {
+            let block = CommentBlock::new("tag_1".to_string()).with_stamp(Stamp::Refuse);
+            let buf = "* x\n  <!--START_SECTION:tag_1-->\n  old\n  <!-- END_SECTION:tag_1 -->\n";
+            let stamped = format!(
+                "* x\n  <!--START_SECTION:tag_1-->\n  * a\n  <!--END_SECTION:tag_1 hash={}-->\n",
+                content_hash("* a")
+            );
+            let block = block.with_indent(true);
+            let replaced = replace_in_str("* a", &block, buf).unwrap();
+            assert_eq!(replaced.content, stamped);
+            assert!(!replace_in_str("* a", &block, &stamped).unwrap().changed);
+            let sections = [(block.clone(), "* a".to_string())];
+            assert_eq!(replace_many_in_str(&sections, buf).unwrap().content, stamped);
+
+            let edited = stamped.replace("* a", "* a, edited");
+            let err = replace_in_str("* b", &block, &edited).unwrap_err();
+            assert!(
+                matches!(&err, ParserError::SectionEdited { end, .. } if end.line == 3),
+                "{err:?}"
+            );
+            let warned = replace_in_str("* b", &block.clone().with_stamp(Stamp::Warn), &edited);
+            assert_eq!(
+                warned.unwrap().content,
+                stamped.replace("* a", "* b").replace(&content_hash("* a"), &content_hash("* b"))
+            );
+            let unstamped = replace_in_str("* b", &block.clone().with_stamp(Stamp::Off), &edited);
+            assert_eq!(unstamped.unwrap().content, stamped.replace("* a", "* b"));
+
+            assert_eq!("refuse".parse::<Stamp>().unwrap(), Stamp::Refuse);
+            assert!("always".parse::<Stamp>().is_err());
+        }
+
+        #[test]
+        fn should_replace_sections_with_other_marker_formats() {
+            for (format, start, end) in [
+                (MarkerFormat::begin_end(), "<!-- BEGIN tag_1 -->", "<!-- END tag_1 -->"),
+                (
+                    MarkerFormat::name_start_end(),
+                    "<!--tag_1:start sort=stars-->",
+                    "<!--tag_1:end-->",
+                ),
+                (
+                    MarkerFormat::blog_post_list(),
+                    "<!-- BLOG-POST-LIST:START -->",
+                    "<!-- BLOG-POST-LIST:END -->",
+                ),
+            ] {
+                let block = CommentBlock::new("tag_1".to_string()).with_format(format.clone());
+                let buf = format!("# Title\n{start}\nold\n{end}\n<!--START_SECTION:tag_1-->\n");
+                let replaced = replace_in_str("* a", &block, &buf).unwrap();
+                assert_eq!(replaced.content, buf.replace("old", "* a"), "{format:?}");
+
+                let block = block.with_stamp(Stamp::Refuse);
+                let stamped = replace_in_str("* a", &block, &buf).unwrap().content;
+                assert!(stamped.contains(&format!(" hash={}", content_hash("* a"))), "{stamped}");
+                assert!(!replace_in_str("* a", &block, &stamped).unwrap().changed, "{format:?}");
+                let err =
+                    replace_in_str("* b", &block, &stamped.replace("* a", "* c")).unwrap_err();
+                assert!(matches!(err, ParserError::SectionEdited { .. }), "{err:?}");
+            }
+
+            let block = CommentBlock::new("outer".to_string())
+                .with_format(MarkerFormat::begin_end())
+                .with_nested(Nested::Preserve);
+            let buf = "<!-- BEGIN outer -->\n<!-- BEGIN inner -->\nkept\n<!-- END inner -->\n\
+                       <!-- END outer -->\n";
+            let text = "new\n<!-- BEGIN inner -->\n<!-- END inner -->";
+            assert_eq!(
+                replace_in_str(text, &block, buf).unwrap().content,
+                "<!-- BEGIN outer -->\nnew\n<!-- BEGIN inner -->\nkept\n<!-- END inner -->\n\
+                 <!-- END outer -->\n"
+            );
+            let block = CommentBlock::new("x".to_string())
+                .with_format(MarkerFormat::name_start_end())
+                .with_insert(Insert::BeforeSection("outer".to_string()));
+            let created = replace_in_str("y", &block, "<!--outer:start-->\n<!--outer:end-->\n");
+            assert_eq!(
+                created.unwrap().content,
+                "<!--x:start-->\ny\n<!--x:end-->\n\n<!--outer:start-->\n<!--outer:end-->\n"
+            );
+        }
+
+        #[test]
+        fn should_extract_what_replace_wrote() {
+            let block = CommentBlock::new("tag_1".to_string());
+            for (block, buf) in [
+                (block.clone(), INITIAL_CONTENT.to_string()),
+                (
+                    block.clone(),
+                    "<!--START_SECTION:tag_1-->\r\n<!--END_SECTION:tag_1-->".to_string(),
+                ),
+                (
+                    block.clone().with_indent(true),
+                    "* a\n  <!--START_SECTION:tag_1-->\n  <!--END_SECTION:tag_1-->\n".to_string(),
+                ),
+            ] {
+                let replaced = replace_in_str("* a\n\n  * b", &block, &buf).unwrap();
+                assert_eq!(extract(&block, &replaced.content).unwrap(), "* a\n\n  * b");
+
+                let extracted = extract(&block, &buf).unwrap();
+                assert!(!replace_in_str(&extracted, &block, &buf).unwrap().changed, "{buf}");
+            }
+
+            let err = extract(&block, "<!--END_SECTION:tag_1-->").unwrap_err();
+            assert!(matches!(err, ParserError::MarkerNotFound { .. }), "{err:?}");
+        }
+
+        #[test]
+        fn should_create_missing_sections_when_asked() {
+            let block = CommentBlock::new("tag_1".to_string()).with_attribute("limit", "2");
+            let (start, end) = ("<!--START_SECTION:tag_1 limit=2-->", "<!--END_SECTION:tag_1-->");
+            let buf = "# Title\n\n## Projects\ntext\n```\n## Projects\n```\n<!--START_SECTION:tag_2-->\n<!--END_SECTION:tag_2-->";
+
+            let err = replace_in_str("new", &block, buf).unwrap_err();
+            assert!(matches!(err, ParserError::MarkerNotFound { .. }), "{err:?}");
+
+            let cases = [
+                (Insert::Append, format!("{buf}\n\n{start}\nnew\n{end}\n")),
+                (
+                    Insert::AfterHeading("Projects".to_string()),
+                    buf.replace(
+                        "## Projects\ntext",
+                        &format!("## Projects\n\n{start}\nnew\n{end}\ntext"),
+                    ),
+                ),
+                (
+                    Insert::BeforeSection("tag_2".to_string()),
+                    buf.replace(
+                        "```\n<!--START",
+                        &format!("```\n{start}\nnew\n{end}\n\n<!--START"),
+                    ),
+                ),
+            ];
+            for (insert, expected) in cases {
+                let block = block.clone().with_insert(insert);
+                let replaced = replace_in_str("new", &block, buf).unwrap();
+                assert_eq!(replaced.content, expected);
+                assert!(replaced.changed && replaced.created);
+                assert_eq!(section_attributes_in_str(&block, buf).unwrap(), *block.attributes());
+
+                let again = replace_in_str("new", &block, &replaced.content).unwrap();
+                assert!(!again.changed && !again.created);
+            }
+
+            let block = block.with_insert("after:### Projects".parse().unwrap());
+            let err = replace_in_str("new", &block, buf).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Cannot create section `tag_1`: heading `### Projects` not found"
+            );
+            assert!("after:".parse::<Insert>().is_err());
+        }
+
+        #[test]
+        fn should_back_up_only_files_that_change() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("README.md");
+            File::create(&path).unwrap().write_all(INITIAL_CONTENT.as_bytes()).unwrap();
+            let backups = Backups::new(dir.path().join("backups"), 3);
+            let block = CommentBlock::new("tag_1".to_string());
+
+            replace_with_backups(TO_UPDATE_WITH, block.clone(), &path, &backups).unwrap();
+            replace_with_backups(TO_UPDATE_WITH, block, &path, &backups).unwrap();
+            let saved = backups.list(&path).unwrap();
+            assert_eq!(saved.len(), 1);
+            assert_eq!(std::fs::read_to_string(&saved[0].path).unwrap(), INITIAL_CONTENT);
+        }
+
+        #[test]
+        fn should_not_clobber_concurrent_modifications() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("README.md");
+            File::create(&path).unwrap().write_all(INITIAL_CONTENT.as_bytes()).unwrap();
+
+            let err = write_atomic_with(&path, None, |writer| {
+                std::fs::write(&path, "edited meanwhile").unwrap();
+                writer.write_all(b"updated")?;
+                Ok(true)
+            })
+            .unwrap_err();
+            assert!(matches!(err, ParserError::ConcurrentModification { .. }), "{err:?}");
+            test_if_written(&path, "edited meanwhile").expect("Should keep the other edit");
+            assert!(lock_path(&std::fs::canonicalize(&path).unwrap()).exists());
+        }
+
+        #[test]
+        fn should_dry_run_without_writing() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("README.md");
+            File::create(&path).unwrap().write_all(INITIAL_CONTENT.as_bytes()).unwrap();
+            let block = CommentBlock::new("tag_1".to_string());
+
+            let outcome = replace_dry_run(TO_UPDATE_WITH, &block, &path).unwrap();
+            test_if_written(&path, INITIAL_CONTENT).expect("Should leave the file untouched");
+            let ReplaceOutcome::Changed { diff } = outcome else {
+                panic!("Should report a change");
+            };
+            assert_eq!(diff.stats(), (6, 0));
+
+            replace(TO_UPDATE_WITH, block.clone(), &path).unwrap();
+            let outcome = replace_dry_run(TO_UPDATE_WITH, &block, &path).unwrap();
+            assert_eq!(outcome, ReplaceOutcome::Unchanged);
+        }
+
+        #[test]
+        fn should_check_section_is_up_to_date() {
+            let block = CommentBlock::new("tag_1".to_string());
+
+            let err = check_in_str(TO_UPDATE_WITH, &block, INITIAL_CONTENT).unwrap_err();
+            assert!(err.is_stale_section());
+            let ParserError::StaleSection { section, diff } = err else { unreachable!() };
+            assert_eq!(section, "tag_1");
+            assert_eq!(diff.stats(), (6, 0));
+
+            let updated = replace_in_str(TO_UPDATE_WITH, &block, INITIAL_CONTENT).unwrap().content;
+            check_in_str(TO_UPDATE_WITH, &block, &updated).expect("Should be up to date");
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn should_keep_permissions_and_follow_symlinks() {
+            use std::os::unix::fs::{symlink, PermissionsExt};
+
+            let dir = tempdir().unwrap();
+            let target = dir.path().join("README.md");
+            let link = dir.path().join("LINK.md");
+            File::create(&target).unwrap().write_all(INITIAL_CONTENT.as_bytes()).unwrap();
+            std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o640)).unwrap();
+            symlink(&target, &link).unwrap();
+
+            replace_par(TO_UPDATE_WITH, CommentBlock::new("tag_1".to_string()), &link).unwrap();
+
+            assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+            let mode = std::fs::metadata(&target).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+            let mut buf = String::new();
+            File::open(&target).unwrap().read_to_string(&mut buf).unwrap();
+            assert!(buf.contains(TO_UPDATE_WITH));
+            // No temporary or lock files are left behind next to the target.
+            let mut names: Vec<_> = std::fs::read_dir(dir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect();
+            names.sort();
+            assert_eq!(names, ["LINK.md", "README.md"]);
+        }
+
         #[test]
         fn quickcheck_get_section_positions() {
             fn prop(text: Text) -> bool {
@@ -450,6 +2644,14 @@ Lorem ipsum dolor sit amet, qui minim labore adipisicing minim sint cillum sint
             quickcheck(prop as fn(Text) -> bool);
         }
 
+        #[test]
+        fn should_not_panic_on_reversed_markers() {
+            let buf = "<!--END_SECTION:tag_1-->\n<!--START_SECTION:tag_1-->";
+            let result =
+                get_block_positions(buf, "<!--START_SECTION:tag_1-->", "<!--END_SECTION:tag_1-->");
+            assert!(result.is_err());
+        }
+
         #[test]
         fn should_get_section_positions() {
             let block = CommentBlock::new("tag_1".to_string());
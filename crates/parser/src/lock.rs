@@ -0,0 +1,126 @@
+//! `lock` is a Rust module for keeping concurrent updates of a file from clobbering each other.
+//!
+//! * [`FileLock`] holds an advisory lock for the duration of an update, so a second run waits for
+//!   the first one to finish. The lock is taken on a lock file in the `.dashboard/locks`
+//!   directory of the working directory, named after the file and a hash of its absolute path,
+//!   e.g. `.dashboard/locks/README.md-<hash>.lock`, since the update renames a new file
+//!   over the original and a lock on the original would be left on the replaced file. Lock files
+//!   are reused rather than removed, as removing one while another run waits on it would let a
+//!   third run lock a new one.
+//! * [`Snapshot`] records the modification time, length and hash of a file when an update reads
+//!   it, to catch writers that don't take the lock, such as an editor, before their changes are
+//!   overwritten.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs::{self, File, OpenOptions, TryLockError},
+    hash::Hasher,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::backup::path_hash;
+
+/// `FileLock` is an exclusive advisory lock on the lock file of a file, released when dropped.
+#[derive(Debug)]
+pub(crate) struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    /// Locks the file at `target`, waiting for the lock if another process holds it.
+    pub(crate) fn acquire(target: &Path) -> super::Result<Self> {
+        let path = lock_path(target);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(&path)?;
+        match file.try_lock() {
+            Ok(()) => (),
+            Err(TryLockError::WouldBlock) => {
+                log::info!("Waiting for another update of {} to finish", target.display());
+                file.lock()?;
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        Ok(Self { file })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // Closing the file releases the lock anyway.
+        let _ = self.file.unlock();
+    }
+}
+
+/// The directory lock files are kept in, relative to the working directory.
+const LOCKS_DIR: &str = ".dashboard/locks";
+
+/// Returns the lock file of `target`, an absolute path, in [`LOCKS_DIR`].
+pub(crate) fn lock_path(target: &Path) -> PathBuf {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    Path::new(LOCKS_DIR).join(format!("{name}-{:016x}.lock", path_hash(target)))
+}
+
+/// `Snapshot` identifies the content of a file at some point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Snapshot {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the file at `path`, reading it in chunks to hash it.
+    pub(crate) fn take(path: &Path) -> super::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut hasher = DefaultHasher::new();
+        loop {
+            let chunk = reader.fill_buf()?;
+            if chunk.is_empty() {
+                break;
+            }
+            hasher.write(chunk);
+            let len = chunk.len();
+            reader.consume(len);
+        }
+
+        Ok(Self { modified: metadata.modified().ok(), len: metadata.len(), hash: hasher.finish() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn should_hold_the_lock_until_dropped() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("README.md");
+        fs::write(&path, "a").unwrap();
+
+        let lock = FileLock::acquire(&path).unwrap();
+        let other = File::options().write(true).open(lock_path(&path)).unwrap();
+        assert!(matches!(other.try_lock(), Err(TryLockError::WouldBlock)));
+        drop(lock);
+        assert!(other.try_lock().is_ok());
+    }
+
+    #[test]
+    fn should_tell_snapshots_of_changed_files_apart() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("README.md");
+        fs::write(&path, "a").unwrap();
+
+        let snapshot = Snapshot::take(&path).unwrap();
+        assert_eq!(Snapshot::take(&path).unwrap(), snapshot);
+        fs::write(&path, "b").unwrap();
+        assert_ne!(Snapshot::take(&path).unwrap(), snapshot);
+    }
+}
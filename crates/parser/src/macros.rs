@@ -11,6 +11,10 @@
 //! Macro `comment_block_dyn` make the `SECTION` parameter dynamic by changing the macro
 //! definition to accept an additional argument for the section name. pass in the section name at
 //! runtime, instead of having it hardcoded in the macro definition.
+//!
+//! Both only write the default `START_SECTION`/`END_SECTION` wording. Markers worded otherwise
+//! are built by a [`CommentBlock`](crate::findrepl::CommentBlock) with a
+//! [`MarkerFormat`](crate::findrepl::MarkerFormat).
 
 /// The macro `comment_block` generates the start and end marker strings of a comment
 /// section in a Markdown file.
@@ -20,7 +24,8 @@
 /// The macro takes in two arguments: `$section_name` and `$marker`.
 /// The first argument, `$section_name`, specifies the name of the comment section.
 /// The second argument, `$marker`, specifies whether it's the start or end marker of the
-/// comment section.
+/// comment section. An optional third argument, `$attributes`, is written after the section
+/// name, e.g. `<!--START_SECTION:repos sort=stars-->`.
 ///
 /// # Internal Notes for Developers
 ///
@@ -35,6 +40,9 @@ macro_rules! comment_block {
     ($section_name:expr, $marker:expr) => {
         format!("<!--{}_SECTION:{}-->", $marker, $section_name)
     };
+    ($section_name:expr, $marker:expr, $attributes:expr) => {
+        format!("<!--{}_SECTION:{} {}-->", $marker, $section_name, $attributes)
+    };
 }
 
 /// Macro `comment_block_dyn` macro accepts three arguments:
@@ -0,0 +1,153 @@
+//! `merge` is a Rust module for regenerating a section without losing the lines written in it by
+//! hand.
+//!
+//! A line of the current section that carries a keep comment, `<!-- keep -->` in Markdown, is
+//! pinned and survives [`Merge::KeepPinned`] replacements:
+//!
+//! * If it links to the same URL as a generated line, e.g. a repo with a note appended to it, it
+//!   stands in for that line, so it follows the generated order and isn't listed twice.
+//! * Otherwise it is kept right after the generated line it followed, matched by URL, or at the
+//!   top of the section if it followed none.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use parser::findrepl::{replace_in_str, CommentBlock, Merge};
+//!
+//! let buf = "<!--START_SECTION:tag_1-->
+//! * [a](https://github.com/u/a)
+//!   Ask before archiving. <!-- keep -->
+//! * [b](https://github.com/u/b) — mirror of a. <!-- keep -->
+//! <!--END_SECTION:tag_1-->
+//! ";
+//! let block = CommentBlock::new("tag_1".to_string()).with_merge(Merge::KeepPinned);
+//! let text = "* [b](https://github.com/u/b)\n* [c](https://github.com/u/c)\n* [a](https://github.com/u/a)";
+//!
+//! assert_eq!(
+//!     replace_in_str(text, &block, buf).unwrap().content,
+//!     "<!--START_SECTION:tag_1-->
+//! * [b](https://github.com/u/b) — mirror of a. <!-- keep -->
+//! * [c](https://github.com/u/c)
+//! * [a](https://github.com/u/a)
+//!   Ask before archiving. <!-- keep -->
+//! <!--END_SECTION:tag_1-->
+//! "
+//! );
+//! ```
+//!
+//! [`Merge::KeepPinned`]: crate::findrepl::Merge::KeepPinned
+
+use std::collections::{HashMap, HashSet};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::style::CommentStyle;
+
+/// The word that pins a line when written in a comment of the section's style.
+const KEEP: &str = "keep";
+
+lazy_static! {
+    /// Matches the target of the first Markdown link of a line, or else its first bare URL.
+    static ref RE_URL: Regex =
+        Regex::new(r"\]\(\s*<?(?P<link>[^\s)>]+)|(?P<bare>https?://[^\s)>\]|]+)").unwrap();
+}
+
+/// Builds the regex matching the keep comment written in `style`, with optional whitespace
+/// inside the comment delimiters.
+fn keep_regex(style: &CommentStyle) -> Regex {
+    let prefix = regex::escape(style.prefix().trim());
+    let pattern = match style.suffix().trim() {
+        "" => format!(r"{prefix}\s*{KEEP}\s*$"),
+        suffix => format!(r"{prefix}\s*{KEEP}\s*{}", regex::escape(suffix)),
+    };
+    Regex::new(&pattern).expect("escaped keep pattern is a valid regex")
+}
+
+/// Returns the URL a line is matched by, see [`RE_URL`].
+fn url(line: &str) -> Option<&str> {
+    let captures = RE_URL.captures(line)?;
+    captures.name("link").or_else(|| captures.name("bare")).map(|m| m.as_str())
+}
+
+/// `merge_pinned` returns the lines of `new`, with the pinned lines of `old` merged in as
+/// described in the [module documentation](self). Both are the content of a section, without its
+/// markers.
+pub(crate) fn merge_pinned(old: &str, new: &str, style: &CommentStyle) -> String {
+    let re_keep = keep_regex(style);
+    let new_urls: HashSet<&str> = new.lines().filter_map(url).collect();
+
+    // Pinned lines standing in for a generated line, by URL.
+    let mut stand_ins: HashMap<&str, &str> = HashMap::new();
+    // Other pinned lines, with the URL of the generated line they follow.
+    let mut pins: Vec<(Option<&str>, &str)> = Vec::new();
+    // URLs of the lines seen so far that are still generated.
+    let mut anchors: Vec<&str> = Vec::new();
+    for line in old.lines() {
+        let url = url(line).filter(|url| new_urls.contains(url));
+        match (re_keep.is_match(line), url) {
+            (true, Some(url)) if !stand_ins.contains_key(url) => {
+                stand_ins.insert(url, line);
+                anchors.push(url);
+            }
+            (true, _) => pins.push((anchors.last().copied(), line)),
+            (false, Some(url)) => anchors.push(url),
+            (false, None) => (),
+        }
+    }
+
+    let pinned_after = |anchor: Option<&str>| -> Vec<&str> {
+        pins.iter().filter(|(after, _)| *after == anchor).map(|(_, line)| *line).collect()
+    };
+    let mut lines = pinned_after(None);
+    let mut placed = HashSet::new();
+    for line in new.lines() {
+        let url = url(line);
+        lines.push(url.and_then(|url| stand_ins.get(url)).copied().unwrap_or(line));
+        if let Some(url) = url.filter(|url| placed.insert(*url)) {
+            lines.extend(pinned_after(Some(url)));
+        }
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_keep_pinned_lines_at_their_place() {
+        let old = "\
+Pinned on top. <!-- keep -->
+* [a](https://github.com/u/a) — old description
+  Note on a. <!--keep-->
+* [b](https://github.com/u/b) — gone
+  Note on b. <!-- keep -->
+* [c](https://github.com/u/c) — manual suffix <!-- keep -->
+* <https://github.com/u/d> dropped";
+        let new = "\
+* [c](https://github.com/u/c) — new description
+* [a](https://github.com/u/a) — new description
+* [e](https://github.com/u/e)";
+        let merged = merge_pinned(old, new, &CommentStyle::Html);
+        assert_eq!(
+            merged,
+            "\
+Pinned on top. <!-- keep -->
+* [c](https://github.com/u/c) — manual suffix <!-- keep -->
+* [a](https://github.com/u/a) — new description
+  Note on a. <!--keep-->
+  Note on b. <!-- keep -->
+* [e](https://github.com/u/e)"
+        );
+        assert_eq!(merge_pinned(&merged, new, &CommentStyle::Html), merged);
+
+        assert_eq!(url("| [x](https://x.io/a) | https://y.io |"), Some("https://x.io/a"));
+        assert_eq!(url("see <https://x.io/a>"), Some("https://x.io/a"));
+        assert!(keep_regex(&CommentStyle::Hash).is_match("foo = 1 # keep"));
+        assert_eq!(merge_pinned("a", "b", &CommentStyle::Html), "b");
+    }
+}
@@ -0,0 +1,577 @@
+//! `scan` is a Rust module for discovering every comment-block section in a document.
+//!
+//! [`scan_sections`] walks the document line by line, pairs each `START_SECTION` marker with its
+//! `END_SECTION` marker, and reports structural problems instead of panicking on them.
+//!
+//! Sections may be nested, as long as an inner section ends before its outer section does.
+//! [`SectionScan::tree`] returns the sections arranged by nesting.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use parser::findrepl::scan_sections;
+//!
+//! let buf = "<!--START_SECTION:a-->\n<!--END_SECTION:a-->\n<!--END_SECTION:b-->";
+//! let scan = scan_sections(buf);
+//! assert_eq!(scan.sections.len(), 1);
+//! assert_eq!(scan.problems.len(), 1);
+//! ```
+
+use std::fmt::{self, Display};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{
+    attrs::{parse_attributes, split_name, Attributes},
+    error::ParserError,
+    format::MarkerFormat,
+    style::CommentStyle,
+};
+
+lazy_static! {
+    /// Matches any HTML start or end marker of the default [`MarkerFormat`], capturing the
+    /// section name in the group of the marker kind.
+    static ref RE_MARKER: Regex = MarkerFormat::section().scan_regex(&CommentStyle::Html);
+}
+
+/// `Section` is a matched pair of start and end markers.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// Name of the section, as written after `START_SECTION:`.
+    pub name: String,
+    /// 0-based line of the start marker.
+    pub start: usize,
+    /// 0-based line of the end marker.
+    pub end: usize,
+    /// Attributes written after the name in the start marker.
+    pub attributes: Attributes,
+}
+
+/// `SectionProblem` is a structural problem found while scanning for sections.
+///
+/// All lines are 0-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SectionProblem {
+    /// A start marker has no matching end marker.
+    UnmatchedStart {
+        /// Name of the section.
+        name: String,
+        /// Line of the start marker.
+        line: usize,
+    },
+    /// An end marker has no matching start marker.
+    OrphanedEnd {
+        /// Name of the section.
+        name: String,
+        /// Line of the end marker.
+        line: usize,
+    },
+    /// A section name is used by more than one section.
+    DuplicateName {
+        /// Name of the section.
+        name: String,
+        /// Line of the start marker of the first section with this name.
+        first: usize,
+        /// Line of the start marker of the duplicate.
+        line: usize,
+    },
+    /// A section that started inside another section is still open when the other one ends.
+    Overlapping {
+        /// Name of the section that started inside `other`.
+        name: String,
+        /// Name of the section it overlaps with.
+        other: String,
+        /// Line of the end marker of `other`.
+        line: usize,
+    },
+    /// The end marker of a section comes before its start marker.
+    OutOfOrder {
+        /// Name of the section.
+        name: String,
+        /// Line of the start marker.
+        start: usize,
+        /// Line of the end marker.
+        end: usize,
+    },
+    /// The attributes of a start marker are malformed.
+    InvalidAttributes {
+        /// Name of the section.
+        name: String,
+        /// Line of the start marker.
+        line: usize,
+        /// Why the attributes could not be parsed.
+        reason: String,
+    },
+}
+
+impl SectionProblem {
+    /// Returns the line the problem was found on: the end marker for
+    /// [`SectionProblem::OutOfOrder`], the offending marker otherwise.
+    pub fn line(&self) -> usize {
+        match self {
+            SectionProblem::UnmatchedStart { line, .. }
+            | SectionProblem::OrphanedEnd { line, .. }
+            | SectionProblem::DuplicateName { line, .. }
+            | SectionProblem::Overlapping { line, .. }
+            | SectionProblem::InvalidAttributes { line, .. } => *line,
+            SectionProblem::OutOfOrder { end, .. } => *end,
+        }
+    }
+
+    /// Returns the description of the problem, without the line it was found on.
+    pub fn message(&self) -> String {
+        match self {
+            SectionProblem::UnmatchedStart { name, .. } => {
+                format!("start of section `{name}` has no end marker")
+            }
+            SectionProblem::OrphanedEnd { name, .. } => {
+                format!("end of section `{name}` has no start marker")
+            }
+            SectionProblem::DuplicateName { name, first, .. } => {
+                format!("section `{name}` is already defined at line {}", first + 1)
+            }
+            SectionProblem::Overlapping { name, other, .. } => {
+                format!("section `{name}` overlaps section `{other}`")
+            }
+            SectionProblem::OutOfOrder { name, start, .. } => {
+                format!("end of section `{name}` comes before its start at line {}", start + 1)
+            }
+            SectionProblem::InvalidAttributes { name, reason, .. } => {
+                format!("section `{name}` has invalid attributes: {reason}")
+            }
+        }
+    }
+}
+
+impl Display for SectionProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line() + 1, self.message())
+    }
+}
+
+/// `SectionScan` holds every section found in a document and its structural problems.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SectionScan {
+    /// Matched sections, ordered by their start marker.
+    pub sections: Vec<Section>,
+    /// Structural problems, ordered by the line they were found on.
+    pub problems: Vec<SectionProblem>,
+}
+
+impl SectionScan {
+    /// Returns `true` if no structural problems were found.
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Returns the sections arranged by nesting: each section holds the sections found between
+    /// its markers, in document order.
+    pub fn tree(&self) -> Vec<SectionTree> {
+        build_tree(&self.sections)
+    }
+
+    /// Returns the sections, or [`ParserError::InvalidSections`] if any problems were found.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the scan found structural problems.
+    pub fn validate(self) -> crate::Result<Vec<Section>> {
+        match self.is_valid() {
+            true => Ok(self.sections),
+            false => Err(ParserError::InvalidSections { problems: self.problems, path: None }),
+        }
+    }
+}
+
+/// `SectionTree` is a section with the sections nested inside it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SectionTree {
+    /// The outer section.
+    pub section: Section,
+    /// Sections directly inside `section`, in document order.
+    pub children: Vec<SectionTree>,
+}
+
+/// Arranges `sections`, ordered by their start marker, into trees by line ranges.
+fn build_tree(sections: &[Section]) -> Vec<SectionTree> {
+    let mut trees = Vec::new();
+    let mut i = 0;
+    while let Some(section) = sections.get(i) {
+        let inner = sections[i + 1..].iter().take_while(|s| s.end < section.end).count();
+        let children = build_tree(&sections[i + 1..i + 1 + inner]);
+        trees.push(SectionTree { section: section.clone(), children });
+        i += 1 + inner;
+    }
+    trees
+}
+
+/// `scan_sections` returns every `START_SECTION`/`END_SECTION` pair in `buf` with its name and
+/// line range, along with any structural problems: unmatched starts, orphaned ends, duplicate
+/// names, crossing sections, and ends that come before their start. Properly nested sections are
+/// not a problem.
+///
+/// Markers are expected to be HTML comments. See [`scan_sections_with`] for other styles.
+pub fn scan_sections(buf: &str) -> SectionScan {
+    scan_with_regex(buf, &RE_MARKER, &CommentStyle::Html, &MarkerFormat::section())
+}
+
+/// `scan_sections_with` is [`scan_sections`] for markers written in the given comment `style`.
+pub fn scan_sections_with(buf: &str, style: &CommentStyle) -> SectionScan {
+    scan_sections_with_format(buf, style, &MarkerFormat::section())
+}
+
+/// `scan_sections_with_format` is [`scan_sections_with`] for markers worded after `format`.
+/// Sections of a format without `{name}` are named after its start template.
+pub fn scan_sections_with_format(
+    buf: &str,
+    style: &CommentStyle,
+    format: &MarkerFormat,
+) -> SectionScan {
+    match (style, format == &MarkerFormat::section()) {
+        (CommentStyle::Html, true) => scan_sections(buf),
+        _ => scan_with_regex(buf, &format.scan_regex(style), style, format),
+    }
+}
+
+fn scan_with_regex(
+    buf: &str,
+    re_marker: &Regex,
+    style: &CommentStyle,
+    format: &MarkerFormat,
+) -> SectionScan {
+    let mut scan = SectionScan::default();
+    // Start markers still waiting for their end marker, innermost last.
+    let mut open: Vec<(String, usize, Attributes)> = Vec::new();
+    // End markers seen before any start marker with the same name.
+    let mut early_ends: Vec<(String, usize)> = Vec::new();
+
+    let code = code_block_lines(buf, style);
+    for (n, line) in buf.lines().enumerate().filter(|&(n, _)| !code[n]) {
+        for caps in re_marker.captures_iter(line) {
+            let (is_start, kind) = match caps.name("start") {
+                Some(name) => (true, (name, caps.name("start_attrs"))),
+                None => (false, (caps.name("end").unwrap(), caps.name("end_attrs"))),
+            };
+            let (name, raw_attributes) = match kind {
+                (_, Some(attrs)) if format.is_nameless() => {
+                    (format.nameless_name(), attrs.as_str())
+                }
+                (name, Some(attrs)) => (name.as_str(), attrs.as_str()),
+                (name, None) => split_name(name.as_str()),
+            };
+            let name = name.to_string();
+            match is_start {
+                true => {
+                    if let Some(i) = early_ends.iter().position(|(end, _)| *end == name) {
+                        let (name, end) = early_ends.remove(i);
+                        scan.problems.push(SectionProblem::OutOfOrder { name, start: n, end });
+                        continue;
+                    }
+                    let attributes = parse_attributes(raw_attributes).unwrap_or_else(|e| {
+                        scan.problems.push(SectionProblem::InvalidAttributes {
+                            name: name.clone(),
+                            line: n,
+                            reason: e.to_string(),
+                        });
+                        Attributes::new()
+                    });
+                    open.push((name, n, attributes));
+                }
+                false => match open.iter().rposition(|(start, ..)| *start == name) {
+                    Some(i) => {
+                        // Sections opened after this one must have ended before it.
+                        scan.problems.extend(open[i + 1..].iter().map(|(inner, ..)| {
+                            SectionProblem::Overlapping {
+                                name: inner.clone(),
+                                other: name.clone(),
+                                line: n,
+                            }
+                        }));
+                        let (name, start, attributes) = open.remove(i);
+                        if let Some(first) = scan.sections.iter().find(|s| s.name == name) {
+                            scan.problems.push(SectionProblem::DuplicateName {
+                                name: name.clone(),
+                                first: first.start,
+                                line: start,
+                            });
+                        }
+                        scan.sections.push(Section { name, start, end: n, attributes });
+                    }
+                    None => early_ends.push((name, n)),
+                },
+            }
+        }
+    }
+
+    scan.problems.extend(
+        open.into_iter().map(|(name, line, _)| SectionProblem::UnmatchedStart { name, line }),
+    );
+    scan.problems.extend(
+        early_ends.into_iter().map(|(name, line)| SectionProblem::OrphanedEnd { name, line }),
+    );
+    scan.sections.sort_by_key(|s| s.start);
+    scan.problems.sort_by_key(SectionProblem::line);
+
+    scan
+}
+
+/// Returns, for each line of `buf`, whether it belongs to a CommonMark code block: a fenced block
+/// opened by at least three backticks or tildes, or an indented block of lines starting with four
+/// spaces or a tab after a blank line. Markers on these lines are examples, not sections.
+///
+/// Indented lines that continue a list item are list content, not code.
+///
+/// Only Markdown, whose markers are written in the [`CommentStyle::Html`] style, has code blocks.
+/// In other files, such as YAML or Rust, indentation is plain structure and no line is code.
+pub(crate) fn code_block_lines(buf: &str, style: &CommentStyle) -> Vec<bool> {
+    let mut code = CodeBlocks::new(style);
+    buf.lines()
+        .enumerate()
+        .map(|(n, line)| {
+            code.is_code(match n {
+                0 => line.trim_start_matches('\u{feff}'),
+                _ => line,
+            })
+        })
+        .collect()
+}
+
+/// `CodeBlocks` follows the code blocks of a document fed to it line by line, for callers that
+/// can't hold the whole document. See [`code_block_lines`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CodeBlocks {
+    /// Whether the document is Markdown, the only kind with code blocks.
+    markdown: bool,
+    /// Fence character and length of the open fenced block.
+    fence: Option<(char, usize)>,
+    in_indented: bool,
+    prev_blank: bool,
+    in_list: bool,
+}
+
+impl CodeBlocks {
+    /// Creates a [`CodeBlocks`] for a document whose markers are written in `style`.
+    pub(crate) fn new(style: &CommentStyle) -> Self {
+        Self {
+            markdown: *style == CommentStyle::Html,
+            fence: None,
+            in_indented: false,
+            prev_blank: true,
+            in_list: false,
+        }
+    }
+
+    /// Returns whether `line`, the next line of the document without its line ending, belongs to
+    /// a code block.
+    pub(crate) fn is_code(&mut self, line: &str) -> bool {
+        if !self.markdown {
+            return false;
+        }
+        if let Some((ch, len)) = self.fence {
+            if let Some((close_ch, close_len, rest)) = parse_fence(line) {
+                if close_ch == ch && close_len >= len && rest.trim().is_empty() {
+                    self.fence = None;
+                }
+            }
+            return true;
+        }
+        if let Some((ch, len, info)) = parse_fence(line) {
+            if ch == '~' || !info.contains('`') {
+                self.fence = Some((ch, len));
+                (self.in_indented, self.prev_blank) = (false, false);
+                return true;
+            }
+        }
+        if line.trim().is_empty() {
+            self.prev_blank = true;
+            return self.in_indented;
+        }
+
+        let indent = indent_width(line);
+        let is_code = indent >= 4 && (self.in_indented || (self.prev_blank && !self.in_list));
+        if !is_code {
+            if is_list_item(line) {
+                self.in_list = true;
+            } else if indent == 0 && self.prev_blank {
+                self.in_list = false;
+            }
+        }
+        (self.in_indented, self.prev_blank) = (is_code, false);
+        is_code
+    }
+}
+
+/// Parses a code fence of at least three backticks or tildes indented by at most three spaces.
+/// Returns the fence character, its length and the text after it.
+fn parse_fence(line: &str) -> Option<(char, usize, &str)> {
+    if indent_width(line) > 3 {
+        return None;
+    }
+    let trimmed = line.trim_start();
+    let ch = trimmed.chars().next().filter(|ch| matches!(ch, '`' | '~'))?;
+    let len = trimmed.chars().take_while(|&c| c == ch).count();
+    (len >= 3).then(|| (ch, len, &trimmed[len..]))
+}
+
+/// Returns the width of the leading whitespace of `line`, counting tabs as four columns.
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| matches!(c, ' ' | '\t'))
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// Returns `true` if `line` starts a bullet or ordered list item.
+fn is_list_item(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+    let rest = match digits {
+        0 => trimmed.strip_prefix(['*', '-', '+']),
+        _ => trimmed[digits..].strip_prefix(['.', ')']),
+    };
+    rest.is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t']))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_scan_every_section() {
+        let buf = "# Title\n<!--START_SECTION:a-->\n* a\n<!--END_SECTION:a-->\n\n\
+                   <!--START_SECTION:b-->\n<!--END_SECTION:b-->\n";
+        let scan = scan_sections(buf);
+
+        assert!(scan.is_valid());
+        assert_eq!(
+            scan.sections,
+            vec![
+                Section { name: "a".to_string(), start: 1, end: 3, ..Default::default() },
+                Section { name: "b".to_string(), start: 5, end: 6, ..Default::default() },
+            ]
+        );
+    }
+
+    #[test]
+    fn should_scan_sections_in_other_comment_styles() {
+        let buf = "[package]\n# START_SECTION:deps\nfoo = 1\n# END_SECTION:deps\n";
+        let scan = scan_sections_with(buf, &CommentStyle::Hash);
+        assert_eq!(
+            scan.sections,
+            vec![Section { name: "deps".to_string(), start: 1, end: 3, ..Default::default() }]
+        );
+
+        let buf = "<!-- START_SECTION: a b -->\n<!--END_SECTION:a b   -->\n";
+        assert_eq!(scan_sections(buf).sections[0].name, "a b");
+
+        let buf = "/* START_SECTION:x */\n/* END_SECTION:x */";
+        assert_eq!(scan_sections_with(buf, &CommentStyle::Block).sections.len(), 1);
+        assert!(scan_sections(buf).sections.is_empty());
+    }
+
+    #[test]
+    fn should_scan_sections_in_other_marker_formats() {
+        let buf = "<!-- a:start sort=stars -->\n<!-- b:start -->\n<!-- b:end -->\n<!--a:end-->\n";
+        let scan =
+            scan_sections_with_format(buf, &CommentStyle::Html, &MarkerFormat::name_start_end());
+        assert!(scan.is_valid(), "{:?}", scan.problems);
+        assert_eq!(scan.tree()[0].children[0].section.name, "b");
+        assert_eq!(scan.sections[0].attributes.get("sort").map(String::as_str), Some("stars"));
+
+        let buf = "# BLOG-POST-LIST:START\n# BLOG-POST-LIST:END\n";
+        let scan =
+            scan_sections_with_format(buf, &CommentStyle::Hash, &MarkerFormat::blog_post_list());
+        assert_eq!(scan.sections[0].name, "BLOG-POST-LIST:START");
+        assert!(scan_sections(buf).sections.is_empty());
+    }
+
+    #[test]
+    fn should_skip_markers_in_code_blocks() {
+        let buf = "Usage:\n\n\
+                   ```md\n<!--START_SECTION:a-->\n<!--END_SECTION:a-->\n```\n\n\
+                   ~~~~\n<!--START_SECTION:b-->\n```\n<!--END_SECTION:b-->\n~~~~\n\n\
+                   \x20   <!--START_SECTION:c-->\n\x20   <!--END_SECTION:c-->\n\n\
+                   <!--START_SECTION:real-->\n<!--END_SECTION:real-->\n";
+        let scan = scan_sections(buf);
+
+        assert!(scan.is_valid(), "{:?}", scan.problems);
+        assert_eq!(
+            scan.sections,
+            vec![Section { name: "real".to_string(), start: 16, end: 17, ..Default::default() }]
+        );
+    }
+
+    #[test]
+    fn should_not_treat_list_content_as_code() {
+        let buf = "* Projects\n\n\
+                   \x20   <!--START_SECTION:nested-->\n\x20   <!--END_SECTION:nested-->\n";
+        assert_eq!(code_block_lines(buf, &CommentStyle::Html), vec![false; 4]);
+        assert_eq!(scan_sections(buf).sections.len(), 1);
+    }
+
+    #[test]
+    fn should_report_structural_problems() {
+        let buf = "<!--END_SECTION:rev-->\n\
+                   <!--START_SECTION:rev-->\n\
+                   <!--START_SECTION:dup-->\n<!--END_SECTION:dup-->\n\
+                   <!--START_SECTION:dup-->\n<!--END_SECTION:dup-->\n\
+                   <!--START_SECTION:outer-->\n<!--START_SECTION:inner-->\n\
+                   <!--END_SECTION:outer-->\n<!--END_SECTION:inner-->\n\
+                   <!--END_SECTION:orphan-->\n\
+                   <!--START_SECTION:open-->";
+        let scan = scan_sections(buf);
+
+        assert_eq!(
+            scan.problems,
+            vec![
+                SectionProblem::OutOfOrder { name: "rev".to_string(), start: 1, end: 0 },
+                SectionProblem::DuplicateName { name: "dup".to_string(), first: 2, line: 4 },
+                SectionProblem::Overlapping {
+                    name: "inner".to_string(),
+                    other: "outer".to_string(),
+                    line: 8,
+                },
+                SectionProblem::OrphanedEnd { name: "orphan".to_string(), line: 10 },
+                SectionProblem::UnmatchedStart { name: "open".to_string(), line: 11 },
+            ]
+        );
+        assert!(scan.validate().is_err());
+    }
+
+    #[test]
+    fn should_scan_nested_sections_as_a_tree() {
+        let buf = "<!--START_SECTION:projects-->\n\
+                   <!--START_SECTION:rust-->\n* a\n<!--END_SECTION:rust-->\n\
+                   <!--START_SECTION:go-->\n<!--START_SECTION:cli-->\n<!--END_SECTION:cli-->\n\
+                   <!--END_SECTION:go-->\n\
+                   <!--END_SECTION:projects-->\n\
+                   <!--START_SECTION:footer-->\n<!--END_SECTION:footer-->\n";
+        let scan = scan_sections(buf);
+        assert!(scan.is_valid(), "{:?}", scan.problems);
+
+        let names = |trees: &[SectionTree]| {
+            trees.iter().map(|t| t.section.name.clone()).collect::<Vec<_>>()
+        };
+        let tree = scan.tree();
+        assert_eq!(names(&tree), ["projects", "footer"]);
+        assert_eq!(names(&tree[0].children), ["rust", "go"]);
+        assert_eq!(names(&tree[0].children[1].children), ["cli"]);
+        assert!(tree[1].children.is_empty());
+    }
+
+    #[test]
+    fn should_scan_marker_attributes() {
+        let buf = "<!--START_SECTION:repos sort=stars limit=10-->\n<!--END_SECTION:repos-->\n\
+                   # START_SECTION:bad title=\"open\n# END_SECTION:bad\n";
+        let scan = scan_sections(buf);
+        assert_eq!(scan.sections[0].name, "repos");
+        assert_eq!(scan.sections[0].attributes["limit"], "10");
+
+        let scan = scan_sections_with(buf, &CommentStyle::Hash);
+        assert_eq!(scan.sections[0].name, "bad");
+        assert!(matches!(scan.problems[..], [SectionProblem::InvalidAttributes { line: 2, .. }]));
+    }
+}
@@ -0,0 +1,109 @@
+//! `style` is a Rust module for the comment syntaxes that section markers can be written in.
+//!
+//! Markdown and HTML files keep their markers in HTML comments, but a generated section can also
+//! live in a `Cargo.toml`, a YAML workflow, a Rust source or a shell script, as long as the markers
+//! use that file's comment syntax:
+//!
+//! | Style                        | Start marker                 |
+//! |------------------------------|------------------------------|
+//! | [`CommentStyle::Html`]        | `<!--START_SECTION:x-->`      |
+//! | [`CommentStyle::Hash`]        | `# START_SECTION:x`           |
+//! | [`CommentStyle::DoubleSlash`] | `// START_SECTION:x`          |
+//! | [`CommentStyle::Block`]       | `/* START_SECTION:x */`       |
+//! | [`CommentStyle::Custom`]      | `{prefix}START_SECTION:x{suffix}` |
+
+use std::path::Path;
+
+/// `CommentStyle` is the comment syntax that wraps the section markers of a [`CommentBlock`].
+///
+/// [`CommentBlock`]: crate::findrepl::CommentBlock
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum CommentStyle {
+    /// HTML and Markdown comments: `<!--START_SECTION:x-->`.
+    #[default]
+    Html,
+    /// Shell, TOML, YAML and Python comments: `# START_SECTION:x`.
+    Hash,
+    /// Rust, C-family, Go and JavaScript line comments: `// START_SECTION:x`.
+    DoubleSlash,
+    /// C-style block comments: `/* START_SECTION:x */`.
+    Block,
+    /// Any other syntax: `{prefix}START_SECTION:x{suffix}`.
+    Custom {
+        /// Text written before the marker keyword.
+        prefix: String,
+        /// Text written after the section name. May be empty for line comments.
+        suffix: String,
+    },
+}
+
+impl CommentStyle {
+    /// Returns the text written before the marker keyword.
+    pub fn prefix(&self) -> &str {
+        match self {
+            CommentStyle::Html => "<!--",
+            CommentStyle::Hash => "# ",
+            CommentStyle::DoubleSlash => "// ",
+            CommentStyle::Block => "/* ",
+            CommentStyle::Custom { prefix, .. } => prefix,
+        }
+    }
+
+    /// Returns the text written after the section name.
+    pub fn suffix(&self) -> &str {
+        match self {
+            CommentStyle::Html => "-->",
+            CommentStyle::Hash | CommentStyle::DoubleSlash => "",
+            CommentStyle::Block => " */",
+            CommentStyle::Custom { suffix, .. } => suffix,
+        }
+    }
+
+    /// Wraps `inner` in the comment delimiters of the style.
+    pub fn wrap(&self, inner: &str) -> String {
+        format!("{}{inner}{}", self.prefix(), self.suffix())
+    }
+
+    /// Picks the comment style for the file at `path` from its extension or, for files such as
+    /// `Makefile` and `Dockerfile`, from its name. Unknown files get [`CommentStyle::Html`].
+    pub fn from_path(path: &Path) -> Self {
+        let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if matches!(file_name, "Makefile" | "Dockerfile" | "Justfile" | ".gitignore") {
+            return CommentStyle::Hash;
+        }
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+        match extension.to_ascii_lowercase().as_str() {
+            "toml" | "yml" | "yaml" | "sh" | "bash" | "zsh" | "fish" | "py" | "rb" | "pl" | "r"
+            | "cfg" | "conf" | "nix" | "mk" => CommentStyle::Hash,
+            "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "go" | "java" | "kt" | "swift" | "cs"
+            | "js" | "jsx" | "ts" | "tsx" | "scss" | "zig" | "dart" => CommentStyle::DoubleSlash,
+            "css" => CommentStyle::Block,
+            _ => CommentStyle::Html,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn should_pick_style_from_path() {
+        assert_eq!(CommentStyle::from_path(Path::new("README.md")), CommentStyle::Html);
+        assert_eq!(CommentStyle::from_path(Path::new("Cargo.toml")), CommentStyle::Hash);
+        assert_eq!(CommentStyle::from_path(Path::new(".github/ci.yml")), CommentStyle::Hash);
+        assert_eq!(CommentStyle::from_path(Path::new("src/lib.rs")), CommentStyle::DoubleSlash);
+        assert_eq!(CommentStyle::from_path(Path::new("style.css")), CommentStyle::Block);
+        assert_eq!(CommentStyle::from_path(Path::new("Makefile")), CommentStyle::Hash);
+        assert_eq!(CommentStyle::from_path(Path::new("NOTES")), CommentStyle::Html);
+    }
+
+    #[test]
+    fn should_wrap_markers() {
+        assert_eq!(CommentStyle::Block.wrap("START_SECTION:x"), "/* START_SECTION:x */");
+        let custom = CommentStyle::Custom { prefix: "-- ".to_string(), suffix: String::new() };
+        assert_eq!(custom.wrap("END_SECTION:x"), "-- END_SECTION:x");
+    }
+}